@@ -0,0 +1,200 @@
+//! Benchmarks for the engine rules, the CSV parser and whole runs of the binary.
+//!
+//! Benchmark ids are stable so results can be compared across changes, e.g. with
+//! `cargo bench -- --save-baseline main` and later `cargo bench -- --baseline main`.
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use payments_engine::{
+    adapters::csv_parser::{build_csv_reader, read_transactions},
+    engine::state::State,
+    models::command::Command,
+};
+use rust_decimal::Decimal;
+use std::{
+    hint::black_box,
+    path::PathBuf,
+    process::{Command as Process, Stdio},
+    time::Duration,
+};
+
+/// Commands applied per iteration of a `process_single_command` benchmark.
+const COMMANDS: u32 = 10_000;
+
+/// Rows parsed per iteration of the CSV benchmark.
+const CSV_ROWS: u32 = 100_000;
+
+fn client(i: u32) -> u16 {
+    (i % u32::from(u16::MAX)) as u16 + 1
+}
+
+fn deposits(count: u32) -> Vec<Command> {
+    (0..count)
+        .map(|i| Command::Deposit {
+            client_id: client(i),
+            tx: i + 1,
+            amount: Decimal::new(10_000 + i64::from(i), 4),
+        })
+        .collect()
+}
+
+/// Commands referencing each deposit of `deposits(count)`, built with `make(client, tx)`.
+fn references(count: u32, make: fn(u16, u32) -> Command) -> Vec<Command> {
+    (0..count).map(|i| make(client(i), i + 1)).collect()
+}
+
+fn state_after(commands: &[Command]) -> State {
+    let mut state = State::new();
+    for cmd in commands {
+        state.process_single_command(cmd.clone());
+    }
+    state
+}
+
+/// Throughput of each command type, applied to a state where it succeeds.
+fn bench_process_single_command(c: &mut Criterion) {
+    let mut group = c.benchmark_group("process_single_command");
+    group.measurement_time(Duration::from_secs(10));
+    group.throughput(Throughput::Elements(u64::from(COMMANDS)));
+
+    let funded = deposits(COMMANDS);
+    let disputes = references(COMMANDS, |client_id, tx| Command::Dispute { client_id, tx });
+    let disputed: Vec<Command> = funded.iter().chain(&disputes).cloned().collect();
+    let withdrawals: Vec<Command> = (0..COMMANDS)
+        .map(|i| Command::Withdrawal {
+            client_id: client(i),
+            tx: COMMANDS + i + 1,
+            amount: Decimal::new(1, 4),
+        })
+        .collect();
+
+    let cases = [
+        ("deposit", Vec::new(), funded.clone()),
+        ("withdrawal", funded.clone(), withdrawals),
+        ("dispute", funded.clone(), disputes),
+        (
+            "resolve",
+            disputed.clone(),
+            references(COMMANDS, |client_id, tx| Command::Resolve { client_id, tx }),
+        ),
+        (
+            "chargeback",
+            disputed,
+            references(COMMANDS, |client_id, tx| Command::Chargeback {
+                client_id,
+                tx,
+            }),
+        ),
+    ];
+
+    for (kind, setup, commands) in cases {
+        let state = state_after(&setup);
+        group.bench_function(kind, |b| {
+            b.iter_batched(
+                || (state.clone(), commands.clone()),
+                |(mut state, commands)| {
+                    for cmd in commands {
+                        state.process_single_command(cmd);
+                    }
+                    state
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+/// Deserializing CSV rows into `TransactionInput`s, with positions.
+fn bench_csv_deserialize(c: &mut Criterion) {
+    let mut data = String::from("type,client,tx,amount\n");
+    for i in 0..CSV_ROWS {
+        let row = match i % 10 {
+            0 => format!("dispute,{},{},\n", client(i), i),
+            1..=3 => format!(
+                "withdrawal,{},{},{}.{:04}\n",
+                client(i),
+                i + 1,
+                i % 50,
+                i % 10_000
+            ),
+            _ => format!(
+                "deposit,{},{},{}.{:04}\n",
+                client(i),
+                i + 1,
+                i % 500,
+                i % 10_000
+            ),
+        };
+        data.push_str(&row);
+    }
+
+    let mut group = c.benchmark_group("csv_deserialize");
+    group.throughput(Throughput::Elements(u64::from(CSV_ROWS)));
+    group.bench_function("transaction_input", |b| {
+        b.iter(|| {
+            let mut reader = build_csv_reader(black_box(data.as_bytes()), false);
+            read_transactions(&mut reader)
+                .filter(|record| record.is_ok())
+                .count()
+        })
+    });
+    group.finish();
+}
+
+/// Input of `rows` generated rows, written once and reused by later runs.
+fn generated_input(rows: u64) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("bench_{}.csv", rows));
+    if path.exists() {
+        return path;
+    }
+
+    let status = Process::new(env!("CARGO_BIN_EXE_generate_transactions"))
+        .args([
+            "--rows",
+            &rows.to_string(),
+            "--clients",
+            "10000",
+            "--seed",
+            "1",
+        ])
+        .arg("--output")
+        .arg(&path)
+        .status()
+        .expect("failed to run generate_transactions");
+    assert!(status.success(), "generate_transactions failed");
+    path
+}
+
+/// Whole runs of the binary over generated files, parsing included and balances discarded.
+fn bench_end_to_end(c: &mut Criterion) {
+    let mut group = c.benchmark_group("end_to_end");
+    group.sample_size(10);
+    group.measurement_time(Duration::from_secs(30));
+
+    for (label, rows) in [("1M", 1_000_000), ("10M", 10_000_000)] {
+        let input = generated_input(rows);
+        group.throughput(Throughput::Elements(rows));
+        group.bench_with_input(BenchmarkId::from_parameter(label), &input, |b, input| {
+            b.iter(|| {
+                let status = Process::new(env!("CARGO_BIN_EXE_payments_engine"))
+                    .arg(input)
+                    .args(["--log-level", "error"])
+                    .stdout(Stdio::null())
+                    .status()
+                    .expect("failed to run payments_engine");
+                assert!(status.success());
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_process_single_command,
+    bench_csv_deserialize,
+    bench_end_to_end
+);
+criterion_main!(benches);
@@ -0,0 +1,64 @@
+//! Command sequences decoded from CSV bytes, at the widest scale so amounts reach the limits of
+//! `Decimal`, applied to `State` under the current rules and the strictest policy. Nothing may
+//! panic, the engine invariants hold after every command, and a rejected command leaves its
+//! client's balances alone.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use payments_engine::{
+    adapters::csv_parser::{build_csv_reader, read_transactions},
+    engine::{policy::Policy, state::State},
+    models::{
+        command::Command,
+        precision::{Precision, Rounding},
+    },
+};
+use rust_decimal::Decimal;
+
+const PRECISION: Precision = Precision {
+    scale: 28,
+    rounding: Rounding::Reject,
+};
+
+const POLICIES: [Policy; 2] = [
+    Policy {
+        allow_negative_available: true,
+        lock_on_chargeback: true,
+    },
+    Policy {
+        allow_negative_available: false,
+        lock_on_chargeback: false,
+    },
+];
+
+/// Balances of a client, with a missing account reading as an empty one.
+fn balances(state: &State, client: u16) -> (Decimal, Decimal, bool) {
+    state.accounts.get(&client).map_or(
+        (Decimal::ZERO, Decimal::ZERO, false),
+        |acc| (acc.available, acc.held, acc.locked),
+    )
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = build_csv_reader(data, false);
+    let commands: Vec<Command> = read_transactions(&mut reader)
+        .flatten()
+        .filter_map(|(_, input)| input.to_command_with_precision(&PRECISION).ok())
+        .collect();
+
+    for policy in POLICIES {
+        let mut state = State::with_policy(policy);
+        for cmd in &commands {
+            let before = balances(&state, cmd.client_id());
+            let outcome = state.apply_command(cmd.clone());
+
+            if let Err(e) = state.check_invariants() {
+                panic!("{} after {:?} under {:?}", e, cmd, policy);
+            }
+            if outcome.is_err() {
+                assert_eq!(balances(&state, cmd.client_id()), before, "{:?}", cmd);
+            }
+        }
+    }
+});
@@ -0,0 +1,50 @@
+//! Arbitrary bytes through the CSV reader and `TransactionInput` conversion, the way the engine
+//! reads its input. Nothing may panic, and a deposit or withdrawal is only ever produced with a
+//! positive amount.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use payments_engine::{
+    adapters::csv_parser::{build_csv_reader, check_header, read_transactions},
+    models::precision::{Precision, Rounding},
+};
+use rust_decimal::Decimal;
+
+const PRECISIONS: [Precision; 4] = [
+    Precision {
+        scale: 4,
+        rounding: Rounding::Reject,
+    },
+    Precision {
+        scale: 0,
+        rounding: Rounding::HalfEven,
+    },
+    Precision {
+        scale: 2,
+        rounding: Rounding::HalfUp,
+    },
+    Precision {
+        scale: 28,
+        rounding: Rounding::Down,
+    },
+];
+
+fuzz_target!(|data: &[u8]| {
+    for strict in [false, true] {
+        let mut reader = build_csv_reader(data, strict);
+        if strict && check_header(&mut reader).is_err() {
+            continue;
+        }
+
+        for (_, input) in read_transactions(&mut reader).flatten() {
+            if let Ok(cmd) = input.to_command() {
+                assert!(cmd.amount().is_none_or(|amount| amount > Decimal::ZERO));
+            }
+            for precision in &PRECISIONS {
+                let _ = input.to_command_with_precision(precision);
+                let _ = input.to_strict_command(precision);
+            }
+        }
+    }
+});
@@ -0,0 +1,9 @@
+#!/bin/sh
+# Seed the corpus of every fuzz target with the CSV fixtures under tests/data.
+set -eu
+cd "$(dirname "$0")"
+
+for target in csv_to_command command_sequence; do
+    mkdir -p "corpus/$target"
+    cp ../tests/data/*.csv "corpus/$target/"
+done
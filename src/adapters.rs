@@ -1,3 +1,10 @@
+pub mod accounts_file;
 pub mod cli;
 pub mod csv_parser;
+pub mod follow;
+pub mod http;
+pub mod input;
+pub mod jsonl_parser;
+pub mod logging;
 pub mod output;
+pub mod stream;
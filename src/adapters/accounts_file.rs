@@ -0,0 +1,148 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+use crate::{
+    adapters::output::{AccountOutput, OutputFormat},
+    engine::checkpoint::read_checkpoint,
+    models::{account::Account, precision::Precision},
+};
+
+/// Balances by client, as written by this engine or supplied by someone else in the same schema.
+pub type Accounts = BTreeMap<u16, AccountOutput>;
+
+/// Engine accounts as they would be written, at the output scale.
+pub fn accounts_from_state(accounts: &HashMap<u16, Account>, precision: &Precision) -> Accounts {
+    accounts
+        .iter()
+        .map(|(client, account)| (*client, AccountOutput::new(account, precision)))
+        .collect()
+}
+
+/// Read an accounts file in any output format, guessed from its extension: `.json` is a JSON
+/// array, `.jsonl` or `.ndjson` one object per line, anything else CSV with a header.
+/// JSON amounts are strings, as the engine writes them.
+pub fn read_accounts_file(path: &str) -> Result<Accounts, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    read_accounts(BufReader::new(file), format_from_path(path))
+        .map_err(|e| format!("Invalid accounts file {}: {}", path, e))
+}
+
+/// Read balances from an accounts file, or from an engine checkpoint written with `--checkpoint`,
+/// at the output scale either way.
+pub fn read_snapshot(path: &str, precision: &Precision) -> Result<Accounts, String> {
+    if let Ok(Some(checkpoint)) = read_checkpoint(path) {
+        return Ok(accounts_from_state(&checkpoint.state.accounts, precision));
+    }
+
+    let mut accounts = read_accounts_file(path)?;
+    for account in accounts.values_mut() {
+        account.available = precision.normalize(account.available);
+        account.held = precision.normalize(account.held);
+        account.total = precision.normalize(account.total);
+    }
+    Ok(accounts)
+}
+
+fn format_from_path(path: &str) -> OutputFormat {
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("json") => OutputFormat::Json,
+        Some("jsonl" | "ndjson") => OutputFormat::Jsonl,
+        _ => OutputFormat::Csv,
+    }
+}
+
+/// Read accounts in the given format; a client listed twice is an error.
+pub fn read_accounts<R: BufRead>(reader: R, format: OutputFormat) -> Result<Accounts, String> {
+    let rows: Vec<AccountOutput> = match format {
+        OutputFormat::Csv => read_csv(reader)?,
+        OutputFormat::Json => serde_json::from_reader(reader).map_err(|e| e.to_string())?,
+        OutputFormat::Jsonl => read_jsonl(reader)?,
+    };
+
+    let mut accounts = Accounts::new();
+    for row in rows {
+        let client = row.client;
+        if accounts.insert(client, row).is_some() {
+            return Err(format!("client {} is listed twice", client));
+        }
+    }
+    Ok(accounts)
+}
+
+fn read_jsonl<R: BufRead>(reader: R) -> Result<Vec<AccountOutput>, String> {
+    let mut rows = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row = serde_json::from_str(&line).map_err(|e| format!("line {}: {}", index + 1, e))?;
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn read_csv<R: Read>(reader: R) -> Result<Vec<AccountOutput>, String> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader)
+        .deserialize()
+        .map(|row| row.map_err(|e| e.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_read_accounts_in_every_format() {
+        let csv = "client,available,held,total,locked\n1, 1.5000,0,1.5,false\n2,0,2,2,true\n";
+        let jsonl = "{\"client\":1,\"available\":\"1.5\",\"held\":\"0\",\"total\":\"1.5\",\"locked\":false,\"deposit_count\":1}\n\n\
+                     {\"client\":2,\"available\":\"0\",\"held\":\"2\",\"total\":\"2\",\"locked\":true}\n";
+        let json = format!("[{}]", jsonl.trim().replace("\n\n", ","));
+
+        let from_csv = read_accounts(csv.as_bytes(), OutputFormat::Csv).unwrap();
+        assert_eq!(from_csv[&1].available, Decimal::new(15, 1));
+        assert!(from_csv[&2].locked);
+        assert_eq!(
+            read_accounts(jsonl.as_bytes(), OutputFormat::Jsonl).unwrap(),
+            from_csv
+        );
+        assert_eq!(
+            read_accounts(json.as_bytes(), OutputFormat::Json).unwrap(),
+            from_csv
+        );
+
+        let twice = "client,available,held,total,locked\n1,0,0,0,false\n1,0,0,0,false\n";
+        assert!(read_accounts(twice.as_bytes(), OutputFormat::Csv).is_err());
+        assert_eq!(format_from_path("bank.ndjson"), OutputFormat::Jsonl);
+    }
+
+    #[test]
+    fn test_read_snapshot_from_checkpoint() {
+        use crate::{
+            engine::{checkpoint::write_checkpoint, state::State},
+            models::{command::Command, error::Position},
+        };
+
+        let path = std::env::temp_dir().join("payments_engine_snapshot.ckpt");
+        let path = path.to_str().unwrap();
+        let mut state = State::new();
+        state.process_single_command(Command::Deposit {
+            client_id: 9,
+            tx: 1,
+            amount: Decimal::new(15, 1),
+        });
+        write_checkpoint(path, Position { line: 2, byte: 22 }, &state).unwrap();
+
+        let accounts = read_snapshot(path, &Precision::default()).unwrap();
+        assert_eq!(accounts[&9].total.to_string(), "1.5000");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,11 +1,882 @@
-/// Parse command-line arguments for input CSV file path
-pub fn parse_file_path_from_cli_args() -> String {
+use std::{fs, str::FromStr};
+
+use clap::{Args, CommandFactory, Parser, Subcommand, error::ErrorKind};
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    adapters::{
+        input::{InputFormat, STDIN_PATH},
+        logging::LogFormat,
+        output::{OutputFormat, SortOrder},
+    },
+    engine::{
+        diff::DiffFormat,
+        journal::{AsOf, DEFAULT_CHECKPOINT_EVERY},
+    },
+    models::precision::{Precision, Rounding},
+};
+
+/// Address the `serve` subcommand listens on by default.
+pub const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:8080";
+/// Seconds between two snapshots of the output while following the input.
+pub const DEFAULT_SNAPSHOT_EVERY: u64 = 10;
+/// Most decimal places a `Decimal` can hold.
+const MAX_SCALE: u32 = 28;
+
+const EXIT_CODES_HELP: &str = "Exit codes:
+  0  Success
+  1  Internal error, such as the engine task failing
+  2  Invalid command line or configuration file
+  3  I/O failure reading input or writing output, a journal or a socket
+  4  Invalid input: rejected under --strict, or `validate` found invalid rows
+  5  Invariant violation in the final balances; nothing was written
+  6  `reconcile` found balances differing from the expected ones";
+
+/// How the process exits. Every failure path maps to one of these, see `--help`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitCode {
+    Success = 0,
+    Internal = 1,
+    /// Same code `clap` uses for command-line errors.
+    Usage = 2,
+    Io = 3,
+    InvalidInput = 4,
+    Invariant = 5,
+    Mismatch = 6,
+}
+
+impl ExitCode {
+    pub fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
+
+/// What the program does with its input.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Mode {
+    /// Process the input and write the final balances.
+    #[default]
+    Process,
+    /// `validate` subcommand: report on the input without writing balances.
+    Validate,
+    /// `statement` subcommand: list one client's transactions and balances.
+    Statement,
+    /// `serve` subcommand: accept transactions and queries over HTTP.
+    Serve,
+    /// `stream` subcommand: accept newline-delimited transactions over a socket.
+    Stream,
+    /// `replay` subcommand: rebuild balances at a point in time from a journal.
+    Replay,
+    /// `reconcile` subcommand: compare the final balances with an expected accounts file.
+    Reconcile,
+    /// `diff` subcommand: compare two accounts files or snapshots.
+    Diff,
+    /// `what-if` subcommand: compare the current rules with a candidate policy.
+    WhatIf,
+}
+
+/// Options accepted on the command line, merged with the configuration file.
+#[derive(Debug, PartialEq)]
+pub struct CliArgs {
+    pub mode: Mode,
+    /// Address to serve the HTTP API on in `serve` mode, or to accept lines on in `stream` mode.
+    pub listen: String,
+    /// Unix domain socket to accept lines on in `stream` mode, instead of `listen`.
+    pub unix_socket: Option<String>,
+    /// Input transactions file to process, `-` for stdin; the journal in `replay` mode.
+    pub input: Option<String>,
+    /// Read the commands from a journal given as `input`, in `what-if` mode.
+    pub from_journal: bool,
+    /// Keep reading rows appended to the input instead of stopping at its end.
+    pub follow: bool,
+    /// File to write the final balances to instead of stdout; rewritten on every snapshot
+    /// while following.
+    pub output: Option<String>,
+    /// Where to save progress while following, and to resume from on restart.
+    pub checkpoint: Option<String>,
+    /// Seconds between two snapshots while following.
+    pub snapshot_every: u64,
+    /// Input encoding; guessed from the file extension when not given.
+    pub input_format: Option<InputFormat>,
+    pub output_format: OutputFormat,
+    pub sort: SortOrder,
+    /// Validate the input schema and stop at the first invalid row.
+    pub strict: bool,
+    /// Decimal places allowed on input amounts and written on output balances.
+    pub precision: Precision,
+    /// Where to write the journal of processed commands, if anywhere.
+    pub journal: Option<String>,
+    /// Where to write a JSON summary of the run, if anywhere.
+    pub summary: Option<String>,
+    /// Number of journaled commands between two checkpoints.
+    pub checkpoint_every: usize,
+    /// Accounts file the final balances are expected to match, in `reconcile` mode.
+    pub expected: Option<String>,
+    /// Later snapshot compared with `input` in `diff` mode.
+    pub after: Option<String>,
+    /// Report format in `diff` and `what-if` modes.
+    pub diff_format: DiffFormat,
+    /// Candidate policy file to compare with the current rules, in `what-if` mode.
+    pub policy: Option<String>,
+    /// Point in time to replay a journal up to.
+    pub as_of: Option<AsOf>,
+    /// Restrict replay output, or the statement, to a single client.
+    pub client: Option<u16>,
+    /// Address to serve Prometheus metrics on while running, e.g. `127.0.0.1:9100`.
+    pub metrics_addr: Option<String>,
+    /// Log filter directive; falls back to `RUST_LOG`.
+    pub log_level: Option<String>,
+    /// Log format; falls back to `PAYMENTS_LOG_FORMAT`.
+    pub log_format: Option<LogFormat>,
+}
+
+impl Default for CliArgs {
+    fn default() -> Self {
+        CliArgs {
+            mode: Mode::Process,
+            listen: DEFAULT_LISTEN_ADDR.to_string(),
+            unix_socket: None,
+            input: None,
+            from_journal: false,
+            follow: false,
+            output: None,
+            checkpoint: None,
+            snapshot_every: DEFAULT_SNAPSHOT_EVERY,
+            input_format: None,
+            output_format: OutputFormat::Csv,
+            sort: SortOrder::Client,
+            strict: false,
+            precision: Precision::default(),
+            journal: None,
+            summary: None,
+            checkpoint_every: DEFAULT_CHECKPOINT_EVERY,
+            expected: None,
+            after: None,
+            diff_format: DiffFormat::Human,
+            policy: None,
+            as_of: None,
+            client: None,
+            metrics_addr: None,
+            log_level: None,
+            log_format: None,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "payments_engine",
+    version,
+    about = "Process client transactions into account balances",
+    after_help = EXIT_CODES_HELP
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// TOML file with defaults for the other options, keyed by long option name
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<String>,
+    /// Log filter directive such as `debug`; falls back to RUST_LOG
+    #[arg(long, global = true, value_name = "FILTER")]
+    log_level: Option<String>,
+    /// Log format [human, json]; falls back to PAYMENTS_LOG_FORMAT
+    #[arg(long, global = true, value_name = "FORMAT")]
+    log_format: Option<LogFormat>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Process transactions and write the final balances (the default)
+    Process(ProcessArgs),
+    /// Report on the input without writing balances
+    Validate(InputArgs),
+    /// List one client's transactions with the balances after each
+    Statement(StatementArgs),
+    /// Accept transactions and queries over HTTP
+    Serve(ServeArgs),
+    /// Accept newline-delimited transactions over TCP or a Unix socket
+    Stream(StreamArgs),
+    /// Rebuild balances at a point in time from a journal
+    Replay(ReplayArgs),
+    /// Process transactions and compare the final balances with an expected accounts file
+    Reconcile(ReconcileArgs),
+    /// Show how balances moved between two accounts files or checkpoints
+    Diff(DiffArgs),
+    /// Replay transactions under the current rules and a candidate policy, and compare them
+    WhatIf(WhatIfArgs),
+}
+
+#[derive(Args, Debug)]
+struct InputArgs {
+    /// Transactions file, `-` for stdin; `.gz` and `.zst` files are decompressed
+    #[arg(
+        value_name = "INPUT",
+        required_unless_present = "input",
+        conflicts_with = "input"
+    )]
+    path: Option<String>,
+    /// Same as the positional INPUT
+    #[arg(short, long, value_name = "PATH")]
+    input: Option<String>,
+    /// Input encoding [csv, jsonl]; guessed from the file extension by default
+    #[arg(long, value_name = "FORMAT")]
+    input_format: Option<InputFormat>,
+    /// Validate the input schema and stop at the first invalid row
+    #[arg(long, overrides_with = "no_strict")]
+    strict: bool,
+    /// Skip invalid rows even when the config file sets `strict`
+    #[arg(long, overrides_with = "strict")]
+    no_strict: bool,
+    #[command(flatten)]
+    precision: PrecisionArgs,
+}
+
+impl InputArgs {
+    /// Whether `--strict` or `--no-strict` was given last, if either was.
+    fn strict_flag(&self) -> Option<bool> {
+        match (self.strict, self.no_strict) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct PrecisionArgs {
+    /// Decimal places allowed on amounts and written on balances [default: 4]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(0..=i64::from(MAX_SCALE)))]
+    scale: Option<u32>,
+    /// Handling of amounts with more decimal places [reject, half-even, half-up, down]
+    #[arg(long, value_name = "MODE")]
+    rounding: Option<Rounding>,
+}
+
+#[derive(Args, Debug)]
+struct OutputArgs {
+    /// Write the final balances to this file instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<String>,
+    /// Balances encoding [csv, json, jsonl]
+    #[arg(long, value_name = "FORMAT")]
+    output_format: Option<OutputFormat>,
+    /// Order of the accounts [client, total, locked]
+    #[arg(long, value_name = "ORDER")]
+    sort: Option<SortOrder>,
+}
+
+#[derive(Args, Debug)]
+struct EngineArgs {
+    /// Write a journal of processed commands, for `replay`
+    #[arg(long, value_name = "PATH")]
+    journal: Option<String>,
+    /// Journaled commands between two state checkpoints [default: 10000]
+    #[arg(long, value_name = "N")]
+    checkpoint_every: Option<usize>,
+    /// Serve Prometheus metrics at /metrics on this address
+    #[arg(long, value_name = "HOST:PORT")]
+    metrics_addr: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct ProcessArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+    /// Write a JSON summary of the run
+    #[arg(long, value_name = "PATH")]
+    summary: Option<String>,
+    /// Keep reading rows appended to the input; requires --output
+    #[arg(long)]
+    follow: bool,
+    /// Save progress while following, and resume from it on restart
+    #[arg(long, value_name = "PATH", requires = "follow")]
+    checkpoint: Option<String>,
+    /// Seconds between two snapshots while following [default: 10]
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    snapshot_every: Option<u64>,
+}
+
+#[derive(Args, Debug)]
+struct StatementArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    /// Client to list transactions for
+    #[arg(long, value_name = "ID")]
+    client: u16,
+    /// Write the statement to this file instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct ServeArgs {
+    /// Address to listen on [default: 127.0.0.1:8080]
+    #[arg(long, value_name = "HOST:PORT")]
+    listen: Option<String>,
+    #[command(flatten)]
+    precision: PrecisionArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+}
+
+#[derive(Args, Debug)]
+struct StreamArgs {
+    #[command(flatten)]
+    server: ServeArgs,
+    /// Accept lines on a Unix domain socket instead of --listen
+    #[arg(long = "unix", value_name = "PATH", conflicts_with = "listen")]
+    unix_socket: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct ReplayArgs {
+    /// Journal written by an earlier run with --journal
+    #[arg(value_name = "JOURNAL")]
+    journal: String,
+    /// Balances after the command read from this input line (the header is line 1)
+    #[arg(long, value_name = "N", required_unless_present = "at_time")]
+    at_row: Option<u64>,
+    /// Balances after the last command not later than this Unix time
+    #[arg(long, value_name = "SECS", conflicts_with = "at_row")]
+    at_time: Option<u64>,
+    /// Only write this client's balances
+    #[arg(long, value_name = "ID")]
+    client: Option<u16>,
+    #[command(flatten)]
+    precision: PrecisionArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct ReconcileArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    /// Expected balances in the output schema; the format follows the extension
+    #[arg(long, value_name = "PATH")]
+    expected: String,
+    /// Write the differences to this file instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct DiffArgs {
+    /// Earlier accounts file or checkpoint
+    #[arg(value_name = "BEFORE")]
+    before: String,
+    /// Later accounts file or checkpoint
+    #[arg(value_name = "AFTER")]
+    after: String,
+    /// Report format [human, csv]
+    #[arg(long, value_name = "FORMAT", default_value = "human")]
+    format: DiffFormat,
+    #[command(flatten)]
+    precision: PrecisionArgs,
+    /// Write the report to this file instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct WhatIfArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    /// Read INPUT as a journal written by an earlier run with --journal
+    #[arg(long)]
+    from_journal: bool,
+    /// TOML file with the candidate rules; rules left out keep their current value
+    #[arg(long, value_name = "PATH")]
+    policy: String,
+    /// Report format [human, csv]
+    #[arg(long, value_name = "FORMAT", default_value = "human")]
+    format: DiffFormat,
+    /// Write the report to this file instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<String>,
+}
+
+/// Defaults read from `--config`, keyed by long option name. Command-line values win.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct Config {
+    #[serde(deserialize_with = "from_str")]
+    input_format: Option<InputFormat>,
+    #[serde(deserialize_with = "from_str")]
+    output_format: Option<OutputFormat>,
+    #[serde(deserialize_with = "from_str")]
+    sort: Option<SortOrder>,
+    strict: Option<bool>,
+    scale: Option<u32>,
+    #[serde(deserialize_with = "from_str")]
+    rounding: Option<Rounding>,
+    journal: Option<String>,
+    checkpoint_every: Option<usize>,
+    metrics_addr: Option<String>,
+    listen: Option<String>,
+    snapshot_every: Option<u64>,
+    log_level: Option<String>,
+    #[serde(deserialize_with = "from_str")]
+    log_format: Option<LogFormat>,
+}
+
+/// Deserialize an option through its `FromStr` implementation, as on the command line.
+fn from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| value.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+impl Config {
+    /// Apply the same bounds as the matching command-line options.
+    fn check(&self) -> Result<(), String> {
+        if let Some(scale) = self.scale.filter(|&scale| scale > MAX_SCALE) {
+            return Err(format!("scale {} is not in 0..={}", scale, MAX_SCALE));
+        }
+        if self.snapshot_every == Some(0) {
+            return Err("snapshot-every must be at least 1".into());
+        }
+        Ok(())
+    }
+}
+
+fn load_config(path: &str) -> Result<Config, String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read config {}: {}", path, e))?;
+    let config: Config =
+        toml::from_str(&text).map_err(|e| format!("Invalid config {}: {}", path, e))?;
+    config
+        .check()
+        .map_err(|e| format!("Invalid config {}: {}", path, e))?;
+    Ok(config)
+}
+
+/// Parse command-line arguments, printing help or usage and exiting when asked or invalid
+pub fn parse_cli_args() -> CliArgs {
     let args: Vec<String> = std::env::args().collect();
 
-    if args.len() != 2 {
-        eprintln!("Usage: {} <transactions.csv>", args[0]);
-        std::process::exit(1);
+    parse_args(&args[1..]).unwrap_or_else(|err| err.exit())
+}
+
+/// Parse the arguments following the program name.
+/// Without a subcommand, the arguments are those of `process`, so `<program> input.csv` works.
+pub fn parse_args(args: &[String]) -> Result<CliArgs, clap::Error> {
+    let subcommands: Vec<String> = Cli::command()
+        .get_subcommands()
+        .map(|cmd| cmd.get_name().to_string())
+        .collect();
+    let implicit_process = first_after_globals(args).is_some_and(|first| {
+        !subcommands.contains(first)
+            && !matches!(
+                first.as_str(),
+                "help" | "-h" | "--help" | "-V" | "--version"
+            )
+    });
+
+    let program = "payments_engine".to_string();
+    let process = implicit_process.then(|| "process".to_string());
+    let cli = Cli::try_parse_from(std::iter::once(program).chain(process).chain(args.to_vec()))?;
+
+    let config = match &cli.config {
+        Some(path) => load_config(path).map_err(usage_error)?,
+        None => Config::default(),
+    };
+    let cli = merge(cli, config);
+    check_follow(&cli).map_err(usage_error)?;
+    Ok(cli)
+}
+
+/// The first argument that is not a global option or its value, which names the subcommand
+/// if one was given.
+fn first_after_globals(args: &[String]) -> Option<&String> {
+    const GLOBALS: [&str; 3] = ["--config", "--log-level", "--log-format"];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if GLOBALS.contains(&arg.as_str()) {
+            args.next();
+        } else if !GLOBALS
+            .iter()
+            .any(|global| arg.starts_with(&format!("{}=", global)))
+        {
+            return Some(arg);
+        }
+    }
+    None
+}
+
+fn usage_error(msg: String) -> clap::Error {
+    Cli::command().error(ErrorKind::ValueValidation, msg)
+}
+
+/// Flatten the parsed subcommand into `CliArgs`, falling back to the config file, then to
+/// the built-in defaults.
+fn merge(cli: Cli, config: Config) -> CliArgs {
+    let mut args = CliArgs {
+        log_level: cli.log_level.or(config.log_level.clone()),
+        log_format: cli.log_format.or(config.log_format),
+        strict: config.strict.unwrap_or(false),
+        ..CliArgs::default()
+    };
+
+    let precision = |args: &mut CliArgs, precision: PrecisionArgs| {
+        if let Some(scale) = precision.scale.or(config.scale) {
+            args.precision.scale = scale;
+        }
+        if let Some(rounding) = precision.rounding.or(config.rounding) {
+            args.precision.rounding = rounding;
+        }
+    };
+    let input = |args: &mut CliArgs, input: InputArgs| {
+        if let Some(strict) = input.strict_flag() {
+            args.strict = strict;
+        }
+        args.input = input.path.or(input.input);
+        args.input_format = input.input_format.or(config.input_format);
+        precision(args, input.precision);
+    };
+    let output = |args: &mut CliArgs, output: OutputArgs| {
+        args.output = output.output;
+        args.output_format = output
+            .output_format
+            .or(config.output_format)
+            .unwrap_or_default();
+        args.sort = output.sort.or(config.sort).unwrap_or_default();
+    };
+    let engine = |args: &mut CliArgs, engine: EngineArgs| {
+        args.journal = engine.journal.or(config.journal.clone());
+        args.metrics_addr = engine.metrics_addr.or(config.metrics_addr.clone());
+        if let Some(every) = engine.checkpoint_every.or(config.checkpoint_every) {
+            args.checkpoint_every = every;
+        }
+    };
+    let server = |args: &mut CliArgs, server: ServeArgs| {
+        if let Some(listen) = server.listen.or(config.listen.clone()) {
+            args.listen = listen;
+        }
+        precision(args, server.precision);
+        output(args, server.output);
+        engine(args, server.engine);
+    };
+
+    match cli.command {
+        Command::Process(process) => {
+            input(&mut args, process.input);
+            output(&mut args, process.output);
+            engine(&mut args, process.engine);
+            args.summary = process.summary;
+            args.follow = process.follow;
+            args.checkpoint = process.checkpoint;
+            if let Some(every) = process.snapshot_every.or(config.snapshot_every) {
+                args.snapshot_every = every;
+            }
+        }
+        Command::Validate(validate) => {
+            args.mode = Mode::Validate;
+            input(&mut args, validate);
+        }
+        Command::Statement(statement) => {
+            args.mode = Mode::Statement;
+            input(&mut args, statement.input);
+            args.client = Some(statement.client);
+            args.output = statement.output;
+        }
+        Command::Serve(serve) => {
+            args.mode = Mode::Serve;
+            server(&mut args, serve);
+        }
+        Command::Stream(stream) => {
+            args.mode = Mode::Stream;
+            server(&mut args, stream.server);
+            args.unix_socket = stream.unix_socket;
+        }
+        Command::Replay(replay) => {
+            args.mode = Mode::Replay;
+            args.input = Some(replay.journal);
+            args.as_of = match (replay.at_row, replay.at_time) {
+                (Some(row), _) => Some(AsOf::Row(row)),
+                (None, time) => time.map(AsOf::Timestamp),
+            };
+            args.client = replay.client;
+            precision(&mut args, replay.precision);
+            output(&mut args, replay.output);
+        }
+        Command::Reconcile(reconcile) => {
+            args.mode = Mode::Reconcile;
+            input(&mut args, reconcile.input);
+            args.expected = Some(reconcile.expected);
+            args.output = reconcile.output;
+        }
+        Command::Diff(diff) => {
+            args.mode = Mode::Diff;
+            args.input = Some(diff.before);
+            args.after = Some(diff.after);
+            args.diff_format = diff.format;
+            precision(&mut args, diff.precision);
+            args.output = diff.output;
+        }
+        Command::WhatIf(what_if) => {
+            args.mode = Mode::WhatIf;
+            input(&mut args, what_if.input);
+            args.from_journal = what_if.from_journal;
+            args.policy = Some(what_if.policy);
+            args.diff_format = what_if.format;
+            args.output = what_if.output;
+        }
+    }
+
+    args
+}
+
+/// Following needs a plain file that can be reopened at an offset, and somewhere other than
+/// stdout to keep replacing the balances.
+fn check_follow(cli: &CliArgs) -> Result<(), String> {
+    if !cli.follow {
+        return Ok(());
+    }
+    let input = cli.input.as_deref().unwrap_or(STDIN_PATH);
+    if input == STDIN_PATH || input.ends_with(".gz") || input.ends_with(".zst") {
+        return Err("--follow requires an uncompressed input file, not stdin".into());
+    }
+    if cli.output.is_none() {
+        return Err("--follow requires --output".into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args_process_and_replay() {
+        let cli = parse_args(&args(&[
+            "in.csv",
+            "--journal",
+            "j.jsonl",
+            "--summary",
+            "s.json",
+        ]))
+        .unwrap();
+        assert_eq!(cli.mode, Mode::Process);
+        assert_eq!(cli.input.as_deref(), Some("in.csv"));
+        assert_eq!(cli.input_format, None);
+        assert_eq!(cli.output_format, OutputFormat::Csv);
+        assert_eq!(cli.sort, SortOrder::Client);
+        assert_eq!(cli.journal.as_deref(), Some("j.jsonl"));
+        assert_eq!(cli.summary.as_deref(), Some("s.json"));
+        assert_eq!(cli.metrics_addr, None);
+        assert_eq!(cli.checkpoint_every, DEFAULT_CHECKPOINT_EVERY);
+
+        let cli = parse_args(&args(&["-", "--input-format", "jsonl", "--strict"])).unwrap();
+        assert_eq!(cli.input.as_deref(), Some(STDIN_PATH));
+        assert!(cli.strict);
+        assert_eq!(cli.mode, Mode::Process);
+        assert_eq!(cli.input_format, Some(InputFormat::Jsonl));
+
+        let cli = parse_args(&args(&["process", "--input", "in.csv", "-o", "out.csv"])).unwrap();
+        assert_eq!(cli.input.as_deref(), Some("in.csv"));
+        assert_eq!(cli.output.as_deref(), Some("out.csv"));
+
+        let cli = parse_args(&args(&[
+            "in.csv",
+            "--log-level",
+            "debug",
+            "--log-format",
+            "json",
+        ]))
+        .unwrap();
+        assert_eq!(cli.log_level.as_deref(), Some("debug"));
+        assert_eq!(cli.log_format, Some(LogFormat::Json));
+
+        // Global options may come before the subcommand, named or implicit
+        let cli = parse_args(&args(&["--log-level", "debug", "validate", "in.csv"])).unwrap();
+        assert_eq!(cli.mode, Mode::Validate);
+        assert_eq!(cli.log_level.as_deref(), Some("debug"));
+        let cli = parse_args(&args(&[
+            "--log-format=json",
+            "replay",
+            "j",
+            "--at-row",
+            "2",
+        ]))
+        .unwrap();
+        assert_eq!(cli.mode, Mode::Replay);
+        let cli = parse_args(&args(&["--log-level", "warn", "in.csv"])).unwrap();
+        assert_eq!(cli.mode, Mode::Process);
+        assert_eq!(cli.input.as_deref(), Some("in.csv"));
+
+        let cli = parse_args(&args(&["validate", "in.csv"])).unwrap();
+        assert_eq!(cli.mode, Mode::Validate);
+        assert_eq!(cli.input.as_deref(), Some("in.csv"));
+
+        let cli = parse_args(&args(&["reconcile", "in.csv", "--expected", "bank.csv"])).unwrap();
+        assert_eq!(cli.mode, Mode::Reconcile);
+        assert_eq!(cli.expected.as_deref(), Some("bank.csv"));
+
+        let cli = parse_args(&args(&["diff", "a.csv", "b.ckpt", "--format", "csv"])).unwrap();
+        assert_eq!(cli.mode, Mode::Diff);
+        assert_eq!(cli.input.as_deref(), Some("a.csv"));
+        assert_eq!(cli.after.as_deref(), Some("b.ckpt"));
+        assert_eq!(cli.diff_format, DiffFormat::Csv);
+
+        let cli = parse_args(&args(&[
+            "what-if",
+            "run.journal",
+            "--from-journal",
+            "--policy",
+            "candidate.toml",
+        ]))
+        .unwrap();
+        assert_eq!(cli.mode, Mode::WhatIf);
+        assert_eq!(cli.input.as_deref(), Some("run.journal"));
+        assert!(cli.from_journal);
+        assert_eq!(cli.policy.as_deref(), Some("candidate.toml"));
+        assert_eq!(cli.diff_format, DiffFormat::Human);
+
+        let cli = parse_args(&args(&["statement", "in.csv", "--client", "3"])).unwrap();
+        assert_eq!(cli.mode, Mode::Statement);
+        assert_eq!(cli.client, Some(3));
+
+        let cli = parse_args(&args(&["serve", "--listen", "0.0.0.0:9000"])).unwrap();
+        assert_eq!(cli.mode, Mode::Serve);
+        assert_eq!(cli.listen, "0.0.0.0:9000");
+        assert_eq!(cli.input, None);
+
+        let cli = parse_args(&args(&["stream", "--unix", "/tmp/engine.sock"])).unwrap();
+        assert_eq!(cli.mode, Mode::Stream);
+        assert_eq!(cli.listen, DEFAULT_LISTEN_ADDR);
+        assert_eq!(cli.unix_socket.as_deref(), Some("/tmp/engine.sock"));
+
+        let cli = parse_args(&args(&["in.csv", "--scale", "2", "--rounding", "half-up"])).unwrap();
+        assert_eq!(cli.precision.scale, 2);
+        assert_eq!(cli.precision.rounding, Rounding::HalfUp);
+
+        let cli = parse_args(&args(&[
+            "replay",
+            "j.jsonl",
+            "--at-row",
+            "5",
+            "--client",
+            "2",
+            "--output-format",
+            "jsonl",
+        ]))
+        .unwrap();
+        assert_eq!(cli.mode, Mode::Replay);
+        assert_eq!(cli.input.as_deref(), Some("j.jsonl"));
+        assert_eq!(cli.as_of, Some(AsOf::Row(5)));
+        assert_eq!(cli.client, Some(2));
+        assert_eq!(cli.output_format, OutputFormat::Jsonl);
+
+        let cli = parse_args(&args(&[
+            "in.csv",
+            "--follow",
+            "--output",
+            "out.csv",
+            "--checkpoint",
+            "in.ckpt",
+        ]))
+        .unwrap();
+        assert!(cli.follow);
+        assert_eq!(cli.output.as_deref(), Some("out.csv"));
+        assert_eq!(cli.checkpoint.as_deref(), Some("in.ckpt"));
+        assert_eq!(cli.snapshot_every, DEFAULT_SNAPSHOT_EVERY);
     }
 
-    args[1].clone()
+    #[test]
+    fn test_config_file_defaults() {
+        let path = std::env::temp_dir().join("payments_engine_cli_config.toml");
+        let path = path.to_str().unwrap();
+        fs::write(
+            path,
+            "output-format = \"json\"\nsort = \"total\"\nstrict = true\nscale = 2\n\
+             journal = \"from-config.jsonl\"\n",
+        )
+        .unwrap();
+
+        let cli = parse_args(&args(&["in.csv", "--config", path, "--sort", "locked"])).unwrap();
+        assert_eq!(cli.output_format, OutputFormat::Json);
+        assert_eq!(cli.sort, SortOrder::Locked);
+        assert!(cli.strict);
+        assert_eq!(cli.precision.scale, 2);
+        assert_eq!(cli.journal.as_deref(), Some("from-config.jsonl"));
+
+        let cli = parse_args(&args(&["--config", path, "validate", "in.csv"])).unwrap();
+        assert_eq!(cli.mode, Mode::Validate);
+        assert!(cli.strict);
+
+        let cli = parse_args(&args(&["in.csv", "--config", path, "--no-strict"])).unwrap();
+        assert!(!cli.strict);
+        let cli = parse_args(&args(&["in.csv", "--no-strict", "--strict"])).unwrap();
+        assert!(cli.strict);
+
+        fs::write(path, "sort = \"random\"\n").unwrap();
+        assert!(parse_args(&args(&["in.csv", "--config", path])).is_err());
+        fs::write(path, "colour = true\n").unwrap();
+        assert!(parse_args(&args(&["in.csv", "--config", path])).is_err());
+        fs::write(path, "scale = 60\n").unwrap();
+        assert!(parse_args(&args(&["in.csv", "--config", path])).is_err());
+        fs::write(path, "snapshot-every = 0\n").unwrap();
+        let follow = ["in.csv", "--follow", "-o", "out.csv", "--config", path];
+        assert!(parse_args(&args(&follow)).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert!(parse_args(&[]).is_err());
+        assert!(parse_args(&args(&["a.csv", "b.csv"])).is_err());
+        assert!(parse_args(&args(&["a.csv", "--bogus"])).is_err());
+        assert!(parse_args(&args(&["a.csv", "--input-format", "xml"])).is_err());
+        assert!(parse_args(&args(&["a.csv", "--sort", "random"])).is_err());
+        assert!(parse_args(&args(&["a.csv", "--scale", "29"])).is_err());
+        assert!(parse_args(&args(&["a.csv", "--rounding", "up"])).is_err());
+        assert!(parse_args(&args(&["a.csv", "--log-format", "xml"])).is_err());
+        assert!(parse_args(&args(&["a.csv", "--config", "/nonexistent.toml"])).is_err());
+        assert!(parse_args(&args(&["replay", "j.jsonl"])).is_err());
+        assert!(parse_args(&args(&["replay", "j", "--at-row", "x"])).is_err());
+        assert!(parse_args(&args(&["statement", "in.csv"])).is_err());
+        assert!(parse_args(&args(&["reconcile", "in.csv"])).is_err());
+        assert!(parse_args(&args(&["diff", "a.csv"])).is_err());
+        assert!(parse_args(&args(&["diff", "a.csv", "b.csv", "--format", "xml"])).is_err());
+        assert!(parse_args(&args(&["what-if", "in.csv"])).is_err());
+        assert!(parse_args(&args(&["validate"])).is_err());
+        assert!(parse_args(&args(&["serve", "in.csv"])).is_err());
+        assert!(parse_args(&args(&["stream", "in.csv"])).is_err());
+        assert!(parse_args(&args(&["stream", "--unix", "s", "--listen", "l"])).is_err());
+        assert!(parse_args(&args(&["in.csv", "--follow"])).is_err());
+        assert!(parse_args(&args(&["-", "--follow", "--output", "o"])).is_err());
+        assert!(parse_args(&args(&["in.csv.gz", "--follow", "--output", "o"])).is_err());
+        assert!(parse_args(&args(&["in.csv", "--checkpoint", "c"])).is_err());
+        assert!(
+            parse_args(&args(&[
+                "in.csv",
+                "--follow",
+                "-o",
+                "o",
+                "--snapshot-every",
+                "0"
+            ]))
+            .is_err()
+        );
+
+        let help = parse_args(&args(&["--help"])).unwrap_err();
+        assert_eq!(help.kind(), ErrorKind::DisplayHelp);
+        assert!(help.to_string().contains("Exit codes:"));
+    }
 }
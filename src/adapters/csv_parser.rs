@@ -1,14 +1,240 @@
-use std::fs::File;
+use std::{io::Read, str::FromStr};
+
+use csv::StringRecord;
+use rust_decimal::Decimal;
+
+use crate::{
+    adapters::input::InputRecord,
+    models::{
+        error::{InputError, InputErrorKind, Position},
+        transaction::{EXTENSION_COLUMNS, REQUIRED_COLUMNS, TransactionInput},
+    },
+};
 
 /// Build CSV reader with desired configuration, Sets the capacity 32k for the buffer used in the CSV reader
-pub fn build_csv_reader(path: &str) -> csv::Reader<File> {
+/// In strict mode every row must have as many fields as the header.
+pub fn build_csv_reader<R: Read>(reader: R, strict: bool) -> csv::Reader<R> {
     csv::ReaderBuilder::new()
         .trim(csv::Trim::All)
-        .flexible(true)
+        .flexible(!strict)
         .buffer_capacity(32 * 1024)
-        .from_path(path)
-        .unwrap_or_else(|e| {
-            eprintln!("Failed to open input file: {}", e);
-            std::process::exit(1);
-        })
+        .from_reader(reader)
+}
+
+/// Check the header is `type,client,tx,amount` followed only by known extension columns
+pub fn check_header<R: Read>(csv_reader: &mut csv::Reader<R>) -> Result<(), InputError> {
+    let headers = csv_reader.headers().map_err(|e| {
+        let kind = InputErrorKind::Malformed(e.to_string());
+        InputError::new(kind, e.position().map(Position::from))
+    })?;
+    let position = Some(Position { line: 1, byte: 0 });
+
+    let required = headers.iter().take(REQUIRED_COLUMNS.len());
+    if !required.eq(REQUIRED_COLUMNS) {
+        let msg = format!(
+            "expected {}, found {}",
+            REQUIRED_COLUMNS.join(","),
+            headers.iter().collect::<Vec<_>>().join(",")
+        );
+        return Err(InputError::new(InputErrorKind::BadHeader(msg), position));
+    }
+
+    let mut extensions = headers.iter().skip(REQUIRED_COLUMNS.len());
+    match extensions.find(|name| !EXTENSION_COLUMNS.contains(name)) {
+        Some(name) => {
+            let msg = format!("unknown column {}", name);
+            Err(InputError::new(InputErrorKind::BadHeader(msg), position))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Iterate over CSV rows as `TransactionInput`s tagged with their position
+pub fn read_transactions<R: Read>(
+    csv_reader: &mut csv::Reader<R>,
+) -> impl Iterator<Item = InputRecord> + '_ {
+    let headers = csv_reader.headers().cloned().unwrap_or_default();
+
+    csv_reader.records().map(move |result| {
+        let record = result.map_err(|e| {
+            let kind = match e.kind() {
+                csv::ErrorKind::UnequalLengths {
+                    expected_len, len, ..
+                } => InputErrorKind::WrongFieldCount {
+                    expected: *expected_len,
+                    found: *len,
+                },
+                _ => InputErrorKind::Malformed(e.to_string()),
+            };
+            InputError::new(kind, e.position().map(Position::from))
+        })?;
+        let position = record
+            .position()
+            .map(Position::from)
+            .unwrap_or(Position { line: 0, byte: 0 });
+
+        record
+            .deserialize::<TransactionInput>(Some(&headers))
+            .map(|input| (position, input))
+            .map_err(|e| {
+                let kind = invalid_field(&record, &headers)
+                    .unwrap_or_else(|| InputErrorKind::Malformed(e.to_string()));
+                InputError::new(kind, Some(position))
+            })
+    })
+}
+
+/// Parse a single header-less CSV line in the `type,client,tx,amount[,timestamp]` layout.
+pub fn parse_line(line: &str) -> Result<TransactionInput, InputErrorKind> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(line.as_bytes());
+    let headers: StringRecord = REQUIRED_COLUMNS.iter().chain(&EXTENSION_COLUMNS).collect();
+
+    let record = match reader.records().next() {
+        Some(Ok(record)) => record,
+        Some(Err(e)) => return Err(InputErrorKind::Malformed(e.to_string())),
+        None => return Err(InputErrorKind::Malformed("empty line".into())),
+    };
+    if record.len() > headers.len() {
+        return Err(InputErrorKind::WrongFieldCount {
+            expected: headers.len() as u64,
+            found: record.len() as u64,
+        });
+    }
+
+    record.deserialize(Some(&headers)).map_err(|e| {
+        invalid_field(&record, &headers).unwrap_or_else(|| InputErrorKind::Malformed(e.to_string()))
+    })
+}
+
+/// Find the column that made a row fail to deserialize, if it is an id or a number.
+fn invalid_field(record: &StringRecord, headers: &StringRecord) -> Option<InputErrorKind> {
+    headers.iter().zip(record.iter()).find_map(|(name, value)| {
+        let valid = match name {
+            "client" => value.parse::<u16>().is_ok(),
+            "tx" => value.parse::<u32>().is_ok(),
+            "amount" => value.is_empty() || Decimal::from_str(value).is_ok(),
+            "timestamp" => value.is_empty() || value.parse::<u64>().is_ok(),
+            _ => true,
+        };
+
+        match name {
+            _ if valid => None,
+            "client" | "tx" => Some(InputErrorKind::BadId(format!("{}: {}", name, value))),
+            _ => Some(InputErrorKind::BadNumber(format!("{}: {}", name, value))),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(input: &str) -> Vec<InputError> {
+        let mut reader = build_csv_reader(input.as_bytes(), false);
+        read_transactions(&mut reader)
+            .filter_map(Result::err)
+            .collect()
+    }
+
+    #[test]
+    fn test_read_transactions_reports_typed_errors_with_position() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,1.0\n\
+                     deposit,x,2,1.0\n\
+                     deposit,1,99999999999,1.0\n\
+                     deposit,1,3,abc\n";
+
+        let errors = errors(input);
+        assert_eq!(errors.len(), 3);
+
+        assert!(
+            matches!(errors[0].kind, InputErrorKind::BadId(ref msg) if msg.starts_with("client"))
+        );
+        assert_eq!(errors[0].position, Some(Position { line: 3, byte: 38 }));
+
+        assert!(matches!(errors[1].kind, InputErrorKind::BadId(ref msg) if msg.starts_with("tx")));
+        assert_eq!(errors[1].position.unwrap().line, 4);
+
+        assert!(
+            matches!(errors[2].kind, InputErrorKind::BadNumber(ref msg) if msg.starts_with("amount"))
+        );
+        assert!(errors[2].to_string().starts_with("line 5, byte "));
+    }
+
+    #[test]
+    fn test_strict_header_and_field_count() {
+        let header_error = |input: &str| {
+            check_header(&mut build_csv_reader(input.as_bytes(), true))
+                .unwrap_err()
+                .kind
+        };
+
+        assert!(
+            check_header(&mut build_csv_reader(
+                "type,client,tx,amount\n".as_bytes(),
+                true
+            ))
+            .is_ok()
+        );
+        assert!(
+            check_header(&mut build_csv_reader(
+                "type, client, tx, amount, timestamp\n".as_bytes(),
+                true
+            ))
+            .is_ok()
+        );
+        assert!(matches!(
+            header_error("client,type,tx,amount\n"),
+            InputErrorKind::BadHeader(_)
+        ));
+        assert!(matches!(
+            header_error("deposit,1,1,1.0\n"),
+            InputErrorKind::BadHeader(_)
+        ));
+        assert_eq!(
+            header_error("type,client,tx,amount,memo\n"),
+            InputErrorKind::BadHeader("unknown column memo".into())
+        );
+
+        let mut reader = build_csv_reader(
+            "type,client,tx,amount\ndeposit,1,1,1.0,extra\n".as_bytes(),
+            true,
+        );
+        let error = read_transactions(&mut reader).next().unwrap().unwrap_err();
+        assert_eq!(
+            error.kind,
+            InputErrorKind::WrongFieldCount {
+                expected: 4,
+                found: 5
+            }
+        );
+        assert_eq!(error.position.unwrap().line, 2);
+    }
+
+    #[test]
+    fn test_parse_single_line() {
+        let input = parse_line("deposit, 1, 2, 3.5").unwrap();
+        assert_eq!(input.timestamp, None);
+        assert!(input.to_command().is_ok());
+
+        let input = parse_line("dispute,1,2,,1700000000").unwrap();
+        assert_eq!(input.timestamp, Some(1_700_000_000));
+
+        assert!(parse_line("dispute,1,2").is_ok());
+        assert!(matches!(
+            parse_line("deposit,x,2,1.0"),
+            Err(InputErrorKind::BadId(_))
+        ));
+        assert!(matches!(
+            parse_line("deposit,1,2,1.0,5,extra"),
+            Err(InputErrorKind::WrongFieldCount {
+                expected: 5,
+                found: 6
+            })
+        ));
+    }
 }
@@ -0,0 +1,126 @@
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+/// How long to wait before looking for new data at the end of a followed file.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Reads a file that keeps growing, like `tail -f`: at the end of the data it waits for
+/// more instead of reporting EOF, until `stop` is set.
+/// Only complete lines are handed out, so a row that is still being appended is never parsed
+/// half-written; an incomplete last line is dropped when stopping.
+pub struct FollowReader<R> {
+    inner: R,
+    stop: Arc<AtomicBool>,
+    /// Bytes read from `inner` that do not end in a newline yet.
+    pending: Vec<u8>,
+    /// Complete lines not handed out yet, starting at `ready_start`.
+    ready: Vec<u8>,
+    ready_start: usize,
+}
+
+impl<R: Read> FollowReader<R> {
+    pub fn new(inner: R, stop: Arc<AtomicBool>) -> Self {
+        FollowReader {
+            inner,
+            stop,
+            pending: Vec::new(),
+            ready: Vec::new(),
+            ready_start: 0,
+        }
+    }
+
+    /// Move everything up to the last newline of `pending` into `ready`.
+    fn release_lines(&mut self) {
+        if let Some(end) = self.pending.iter().rposition(|&b| b == b'\n') {
+            self.ready.clear();
+            self.ready.extend(self.pending.drain(..=end));
+            self.ready_start = 0;
+        }
+    }
+}
+
+impl<R: Read> Read for FollowReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut chunk = [0u8; 8 * 1024];
+
+        while self.ready_start == self.ready.len() {
+            match self.inner.read(&mut chunk)? {
+                0 if self.stop.load(Ordering::Relaxed) => return Ok(0),
+                0 => std::thread::sleep(POLL_INTERVAL),
+                n => {
+                    self.pending.extend_from_slice(&chunk[..n]);
+                    self.release_lines();
+                }
+            }
+        }
+
+        let available = &self.ready[self.ready_start..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.ready_start += n;
+        Ok(n)
+    }
+}
+
+/// Seeking drops any buffered data, so a resumed reader starts cleanly at the new offset.
+impl<R: Read + Seek> Seek for FollowReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pending.clear();
+        self.ready.clear();
+        self.ready_start = 0;
+        self.inner.seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_follow_reader_hands_out_complete_lines_only() {
+        let stop = Arc::new(AtomicBool::new(true));
+        let mut reader = FollowReader::new(Cursor::new(b"a,1\nb,2\nc,3".to_vec()), stop);
+
+        let mut out = String::new();
+        reader.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "a,1\nb,2\n");
+
+        reader.seek(SeekFrom::Start(4)).unwrap();
+        let mut out = String::new();
+        reader.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "b,2\n");
+    }
+
+    #[test]
+    fn test_follow_reader_waits_for_appended_data() {
+        let path = std::env::temp_dir().join("payments_engine_follow_reader.csv");
+        std::fs::write(&path, "a,1\n").unwrap();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let file = std::fs::File::open(&path).unwrap();
+        let mut reader = FollowReader::new(file, stop.clone());
+
+        let appender = std::thread::spawn({
+            let path = path.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(50));
+                let mut file = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+                io::Write::write_all(&mut file, b"b,2\n").unwrap();
+                std::thread::sleep(POLL_INTERVAL * 2);
+                stop.store(true, Ordering::Relaxed);
+            }
+        });
+
+        let mut out = String::new();
+        reader.read_to_string(&mut out).unwrap();
+        appender.join().unwrap();
+        assert_eq!(out, "a,1\nb,2\n");
+    }
+}
@@ -0,0 +1,327 @@
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, State as AxumState},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::Serialize;
+use tokio::net::TcpListener;
+use tracing::{error, info};
+
+use crate::{
+    adapters::{
+        jsonl_parser::parse_line,
+        output::{SortOrder, json_output, sort_accounts},
+    },
+    engine::{
+        metrics::Metrics,
+        runner::{self, SubmitOutcome, Submitter},
+    },
+    models::{account::Account, error::InputErrorKind, transaction::TransactionStatus},
+};
+
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Routes exposing live metrics in the Prometheus text format.
+pub fn metrics_router(metrics: Arc<Metrics>) -> Router {
+    Router::new()
+        .route("/metrics", get(render_metrics))
+        .with_state(metrics)
+}
+
+async fn render_metrics(AxumState(metrics): AxumState<Arc<Metrics>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+        metrics.render(),
+    )
+}
+
+/// Outcome of a submitted transaction.
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum SubmitResponse {
+    Applied,
+    /// The engine refused the command, see `Rejection`.
+    Rejected {
+        reason: &'static str,
+    },
+    /// The body is not a valid transaction, see `InputErrorKind`.
+    Invalid {
+        reason: &'static str,
+        message: String,
+    },
+}
+
+#[derive(Serialize)]
+struct TransactionResponse {
+    tx: u32,
+    client: u16,
+    /// Absent once charged back, as the engine no longer keeps the amount.
+    #[serde(
+        with = "rust_decimal::serde::str_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    amount: Option<rust_decimal::Decimal>,
+    status: &'static str,
+}
+
+/// Routes for submitting transactions and reading accounts from a running engine.
+pub fn api_router(state: Submitter) -> Router {
+    Router::new()
+        .route("/transactions", post(post_transaction))
+        .route("/transactions/{tx}", get(get_transaction))
+        .route("/accounts", get(get_accounts))
+        .route("/accounts/{client}", get(get_account))
+        .with_state(state)
+}
+
+/// Accepts the same fields as a JSON Lines input row; unknown fields and amounts on
+/// dispute, resolve and chargeback are rejected as in strict mode.
+async fn post_transaction(AxumState(api): AxumState<Submitter>, body: String) -> Response {
+    let invalid = |kind: InputErrorKind| {
+        let body = SubmitResponse::Invalid {
+            reason: kind.as_str(),
+            message: kind.to_string(),
+        };
+        (StatusCode::BAD_REQUEST, Json(body)).into_response()
+    };
+
+    let input = match parse_line(&body, true) {
+        Ok(input) => input,
+        Err(kind) => return invalid(kind),
+    };
+
+    match api.submit(&input).await {
+        SubmitOutcome::Applied => (StatusCode::OK, Json(SubmitResponse::Applied)).into_response(),
+        SubmitOutcome::Rejected(rejection) => {
+            let body = SubmitResponse::Rejected {
+                reason: rejection.as_str(),
+            };
+            (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response()
+        }
+        SubmitOutcome::Invalid(kind) => invalid(kind),
+        SubmitOutcome::Stopped => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+async fn get_transaction(AxumState(api): AxumState<Submitter>, Path(tx): Path<u32>) -> Response {
+    let found = runner::query(api.engine(), move |state| match state.transaction(tx) {
+        Some(record) => Some(TransactionResponse {
+            tx,
+            client: record.client_id,
+            amount: Some(record.amount),
+            status: record.status.as_str(),
+        }),
+        None => state.charged_back(tx).map(|client| TransactionResponse {
+            tx,
+            client,
+            amount: None,
+            status: TransactionStatus::ChargedBack.as_str(),
+        }),
+    })
+    .await;
+
+    match found {
+        Some(Some(transaction)) => Json(transaction).into_response(),
+        Some(None) => StatusCode::NOT_FOUND.into_response(),
+        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+async fn get_accounts(AxumState(api): AxumState<Submitter>) -> Response {
+    let accounts = runner::query(api.engine(), |state| {
+        sort_accounts(&state.accounts, SortOrder::Client)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<Account>>()
+    })
+    .await;
+
+    match accounts {
+        Some(accounts) => {
+            let output: Vec<_> = accounts
+                .iter()
+                .map(|account| json_output(account, api.precision()))
+                .collect();
+            Json(output).into_response()
+        }
+        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+async fn get_account(AxumState(api): AxumState<Submitter>, Path(client): Path<u16>) -> Response {
+    let account = runner::query(api.engine(), move |state| {
+        state.accounts.get(&client).cloned()
+    })
+    .await;
+
+    match account {
+        Some(Some(account)) => Json(json_output(&account, api.precision())).into_response(),
+        Some(None) => StatusCode::NOT_FOUND.into_response(),
+        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+/// Serve the API on `addr` until Ctrl-C, then stop accepting requests.
+pub async fn serve_api(addr: &str, state: Submitter) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Serving API on http://{}", listener.local_addr()?);
+
+    axum::serve(listener, api_router(state))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+}
+
+/// Bind `addr` and serve `router` on it in a background task.
+pub async fn spawn_server(addr: &str, router: Router) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Listening on http://{}", listener.local_addr()?);
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            error!("HTTP server error: {}", e);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::precision::Precision;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Send a raw HTTP/1.1 request and return the whole response.
+    async fn request(addr: std::net::SocketAddr, request: &str) -> String {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = metrics_router(Arc::new(Metrics::default()));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let response = request(
+            addr,
+            "GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("content-type: text/plain; version=0.0.4"));
+        assert!(response.contains("payments_open_disputes 0\n"));
+    }
+
+    async fn get(addr: std::net::SocketAddr, path: &str) -> String {
+        let head = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        );
+        request(addr, &head).await
+    }
+
+    async fn post(addr: std::net::SocketAddr, path: &str, body: &str) -> String {
+        let head = format!(
+            "POST {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            path,
+            body.len(),
+            body
+        );
+        request(addr, &head).await
+    }
+
+    #[tokio::test]
+    async fn test_api_submit_and_query() {
+        let (cmd_tx, _engine) = runner::setup_engine(Default::default());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = api_router(Submitter::new(cmd_tx, Precision::default()));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let response = post(
+            addr,
+            "/transactions",
+            r#"{"type":"deposit","client":1,"tx":1,"amount":"10.5"}"#,
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with(r#"{"status":"applied"}"#));
+
+        let response = post(
+            addr,
+            "/transactions",
+            r#"{"type":"withdrawal","client":1,"tx":2,"amount":"20"}"#,
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 422"));
+        assert!(response.ends_with(r#"{"status":"rejected","reason":"insufficient_funds"}"#));
+
+        let response = post(
+            addr,
+            "/transactions",
+            r#"{"type":"dispute","client":1,"tx":1,"amount":"1"}"#,
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 400"));
+        assert!(response.contains(r#""reason":"unexpected_amount""#));
+
+        post(
+            addr,
+            "/transactions",
+            r#"{"type":"dispute","client":1,"tx":1}"#,
+        )
+        .await;
+
+        let response = get(addr, "/transactions/1").await;
+        assert!(response.ends_with(r#"{"tx":1,"client":1,"amount":"10.5","status":"disputed"}"#));
+        assert!(
+            get(addr, "/transactions/2")
+                .await
+                .starts_with("HTTP/1.1 404")
+        );
+
+        let response = get(addr, "/accounts/1").await;
+        assert!(response.contains(r#""available":"0.0000","held":"10.5000","total":"10.5000""#));
+        assert!(get(addr, "/accounts/2").await.starts_with("HTTP/1.1 404"));
+
+        let response = get(addr, "/accounts").await;
+        assert!(response.contains(r#"[{"client":1,"#));
+    }
+
+    #[tokio::test]
+    async fn test_api_finds_charged_back_transaction() {
+        let (cmd_tx, _engine) = runner::setup_engine(Default::default());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = api_router(Submitter::new(cmd_tx, Precision::default()));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        for body in [
+            r#"{"type":"deposit","client":1,"tx":1,"amount":"3"}"#,
+            r#"{"type":"dispute","client":1,"tx":1}"#,
+            r#"{"type":"chargeback","client":1,"tx":1}"#,
+        ] {
+            let response = post(addr, "/transactions", body).await;
+            assert!(
+                response.ends_with(r#"{"status":"applied"}"#),
+                "{}",
+                response
+            );
+        }
+
+        let response = get(addr, "/transactions/1").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with(r#"{"tx":1,"client":1,"status":"charged_back"}"#));
+    }
+}
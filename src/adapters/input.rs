@@ -0,0 +1,136 @@
+use flate2::read::MultiGzDecoder;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::models::{
+    error::{InputError, Position},
+    transaction::TransactionInput,
+};
+
+/// A parsed input record tagged with its position in the source, or why it was unreadable.
+pub type InputRecord = Result<(Position, TransactionInput), InputError>;
+
+/// Path that stands for standard input.
+pub const STDIN_PATH: &str = "-";
+
+/// Open the input for reading: `-` is stdin, and `.gz` / `.zst` files are decompressed on the fly.
+pub fn open_input(path: &str) -> io::Result<Box<dyn Read>> {
+    if path == STDIN_PATH {
+        return Ok(Box::new(io::stdin().lock()));
+    }
+
+    let file = BufReader::new(File::open(path)?);
+
+    match Compression::from_path(path) {
+        Some(Compression::Gzip) => Ok(Box::new(MultiGzDecoder::new(file))),
+        Some(Compression::Zstd) => Ok(Box::new(zstd::Decoder::with_buffer(file)?)),
+        None => Ok(Box::new(file)),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn from_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Some(Compression::Gzip),
+            Some("zst") => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Supported encodings of the transactions input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputFormat {
+    Csv,
+    Jsonl,
+}
+
+impl InputFormat {
+    /// Guess the format from the file extension, ignoring any compression suffix.
+    /// Defaults to CSV, which is also what stdin is assumed to carry.
+    pub fn from_path(path: &str) -> Self {
+        let path = Path::new(path);
+        let path = match Compression::from_path(&path.to_string_lossy()) {
+            Some(_) => Path::new(path.file_stem().unwrap_or_default()),
+            None => path,
+        };
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("jsonl" | "ndjson") => InputFormat::Jsonl,
+            _ => InputFormat::Csv,
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "jsonl" => Ok(InputFormat::Jsonl),
+            _ => Err(format!("Unknown input format: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_format_selection() {
+        assert_eq!(InputFormat::from_path("feed.jsonl"), InputFormat::Jsonl);
+        assert_eq!(InputFormat::from_path("feed.ndjson"), InputFormat::Jsonl);
+        assert_eq!(InputFormat::from_path("transactions.csv"), InputFormat::Csv);
+        assert_eq!(InputFormat::from_path("transactions"), InputFormat::Csv);
+        assert_eq!(InputFormat::from_path("feed.jsonl.zst"), InputFormat::Jsonl);
+        assert_eq!(
+            InputFormat::from_path("archive/feed.csv.gz"),
+            InputFormat::Csv
+        );
+        assert_eq!(InputFormat::from_path(STDIN_PATH), InputFormat::Csv);
+        assert_eq!("jsonl".parse(), Ok(InputFormat::Jsonl));
+        assert!("xml".parse::<InputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_open_input_decompresses() {
+        use flate2::{Compression as GzLevel, write::GzEncoder};
+        use std::io::Write;
+
+        let content = "type,client,tx,amount\ndeposit,1,1,1.0\n";
+        let dir = std::env::temp_dir();
+
+        let gz_path = dir.join("payments_engine_open_input.csv.gz");
+        let mut encoder = GzEncoder::new(File::create(&gz_path).unwrap(), GzLevel::default());
+        encoder.write_all(content.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let zst_path = dir.join("payments_engine_open_input.csv.zst");
+        let compressed = zstd::encode_all(content.as_bytes(), 0).unwrap();
+        std::fs::write(&zst_path, compressed).unwrap();
+
+        for path in [&gz_path, &zst_path] {
+            let mut decoded = String::new();
+            open_input(path.to_str().unwrap())
+                .unwrap()
+                .read_to_string(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, content);
+            std::fs::remove_file(path).unwrap();
+        }
+
+        assert!(open_input("does/not/exist.csv").is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::{io::BufRead, str::FromStr};
+
+use crate::{
+    adapters::input::InputRecord,
+    models::{
+        error::{InputError, InputErrorKind, Position},
+        transaction::{EXTENSION_COLUMNS, REQUIRED_COLUMNS, TransactionInput},
+    },
+};
+
+/// Reads one JSON transaction object per line, using the same field names as the CSV header.
+/// In strict mode, fields outside the schema are rejected.
+pub struct JsonlReader<R: BufRead> {
+    reader: R,
+    strict: bool,
+    buffer: Vec<u8>,
+    line_number: u64,
+    byte_offset: u64,
+}
+
+impl<R: BufRead> JsonlReader<R> {
+    pub fn new(reader: R, strict: bool) -> Self {
+        JsonlReader {
+            reader,
+            strict,
+            buffer: Vec::new(),
+            line_number: 0,
+            byte_offset: 0,
+        }
+    }
+
+    /// Number rows as if `reader` started at `position` of the original input.
+    pub fn starting_at(mut self, position: Position) -> Self {
+        self.line_number = position.line.saturating_sub(1);
+        self.byte_offset = position.byte;
+        self
+    }
+}
+
+impl<R: BufRead> Iterator for JsonlReader<R> {
+    type Item = InputRecord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buffer.clear();
+            let position = Position {
+                line: self.line_number + 1,
+                byte: self.byte_offset,
+            };
+
+            let read = self.reader.read_until(b'\n', &mut self.buffer);
+            if matches!(read, Ok(0)) {
+                return None;
+            }
+
+            // On an I/O failure the buffer still holds the bytes consumed so far
+            self.line_number += 1;
+            self.byte_offset += self.buffer.len() as u64;
+
+            let line = read
+                .map_err(|e| e.to_string())
+                .and_then(|_| std::str::from_utf8(&self.buffer).map_err(|e| e.to_string()));
+            let line = match line {
+                Ok(line) => line,
+                Err(message) => {
+                    let kind = InputErrorKind::Malformed(message);
+                    return Some(Err(InputError::new(kind, Some(position))));
+                }
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            return Some(
+                parse_line(line, self.strict)
+                    .map(|input| (position, input))
+                    .map_err(|kind| InputError::new(kind, Some(position))),
+            );
+        }
+    }
+}
+
+/// Parse a single JSON object into a `TransactionInput`.
+/// Numeric amounts are accepted alongside strings and parsed from their exact textual form,
+/// including exponent form, never going through a float.
+pub fn parse_line(line: &str, strict: bool) -> Result<TransactionInput, InputErrorKind> {
+    let mut value: Value =
+        serde_json::from_str(line).map_err(|e| InputErrorKind::Malformed(e.to_string()))?;
+
+    if let (true, Some(object)) = (strict, value.as_object()) {
+        let known = |name: &String| {
+            REQUIRED_COLUMNS.contains(&name.as_str()) || EXTENSION_COLUMNS.contains(&name.as_str())
+        };
+        if let Some(name) = object.keys().find(|name| !known(name)) {
+            return Err(InputErrorKind::UnknownField(name.clone()));
+        }
+    }
+
+    for (field, max) in [("client", u64::from(u16::MAX)), ("tx", u64::from(u32::MAX))] {
+        match value.get(field) {
+            Some(id) if id.as_u64().is_some_and(|id| id <= max) => {}
+            Some(id) => return Err(InputErrorKind::BadId(format!("{}: {}", field, id))),
+            None => {} // reported as a missing field below
+        }
+    }
+
+    match value.get("amount") {
+        Some(Value::Number(number)) => {
+            // With `arbitrary_precision` this is the number as written, e.g. `1e-7`
+            let text = number.to_string();
+            let amount = Decimal::from_str(&text)
+                .or_else(|_| Decimal::from_scientific(&text))
+                .map_err(|_| InputErrorKind::BadNumber(format!("amount: {}", text)))?;
+            value["amount"] = Value::String(amount.to_string());
+        }
+        Some(Value::String(text)) => {
+            let trimmed = text.trim();
+            if trimmed.parse::<Decimal>().is_err() {
+                return Err(InputErrorKind::BadNumber(format!("amount: {}", text)));
+            }
+            value["amount"] = Value::String(trimmed.to_string());
+        }
+        Some(Value::Null) | None => {}
+        Some(other) => return Err(InputErrorKind::BadNumber(format!("amount: {}", other))),
+    }
+
+    serde_json::from_value(value).map_err(|e| InputErrorKind::Malformed(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        command::Command,
+        precision::{Precision, Rounding},
+    };
+
+    #[test]
+    fn test_jsonl_reader_parses_rows_and_reports_bad_lines() {
+        let input = r#"{"type":"deposit","client":1,"tx":1,"amount":"1.5"}
+{"type":"withdrawal","client":1,"tx":2,"amount":0.25,"timestamp":1700000000}
+
+not json
+{"type":"dispute","client":1,"tx":1}
+"#;
+
+        let records: Vec<_> = JsonlReader::new(input.as_bytes(), false).collect();
+        assert_eq!(records.len(), 4);
+
+        let (position, deposit) = records[0].as_ref().unwrap();
+        assert_eq!(*position, Position { line: 1, byte: 0 });
+        match deposit.to_command().unwrap() {
+            Command::Deposit { amount, .. } => {
+                assert_eq!(amount, Decimal::from_str("1.5").unwrap())
+            }
+            _ => panic!("Expected deposit"),
+        }
+
+        let (position, withdrawal) = records[1].as_ref().unwrap();
+        assert_eq!(position.line, 2);
+        assert_eq!(withdrawal.timestamp, Some(1700000000));
+        match withdrawal.to_command().unwrap() {
+            Command::Withdrawal { amount, .. } => {
+                assert_eq!(amount, Decimal::from_str("0.25").unwrap())
+            }
+            _ => panic!("Expected withdrawal"),
+        }
+
+        let error = records[2].as_ref().unwrap_err();
+        assert!(matches!(error.kind, InputErrorKind::Malformed(_)));
+        assert_eq!(error.position.unwrap().line, 4);
+
+        let (position, dispute) = records[3].as_ref().unwrap();
+        assert_eq!(position.line, 5);
+        assert!(matches!(
+            dispute.to_command(),
+            Ok(Command::Dispute { tx: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_line_accepts_numeric_amounts_in_exponent_form() {
+        for (json, expected) in [("1e-7", "0.0000001"), ("2.5E3", "2500"), ("1.25", "1.25")] {
+            let line = format!(
+                r#"{{"type":"deposit","client":1,"tx":1,"amount":{}}}"#,
+                json
+            );
+            let input = parse_line(&line, false).unwrap();
+            match input.to_command_with_precision(&Precision {
+                scale: 8,
+                rounding: Rounding::Reject,
+            }) {
+                Ok(Command::Deposit { amount, .. }) => {
+                    assert_eq!(amount, Decimal::from_str(expected).unwrap(), "{}", json)
+                }
+                other => panic!("Expected deposit for {}, got {:?}", json, other),
+            }
+        }
+
+        // Digits past what an f64 holds are kept
+        for json in ["0.1000000000000000055", "12345678901234567890.25"] {
+            let line = format!(
+                r#"{{"type":"deposit","client":1,"tx":1,"amount":{}}}"#,
+                json
+            );
+            let input = parse_line(&line, false).unwrap();
+            let precision = Precision {
+                scale: 28,
+                rounding: Rounding::Reject,
+            };
+            match input.to_command_with_precision(&precision) {
+                Ok(Command::Deposit { amount, .. }) => {
+                    assert_eq!(amount, Decimal::from_str(json).unwrap())
+                }
+                other => panic!("Expected deposit for {}, got {:?}", json, other),
+            }
+        }
+
+        let line = r#"{"type":"deposit","client":1,"tx":1,"amount":1e40}"#;
+        assert!(matches!(
+            parse_line(line, false),
+            Err(InputErrorKind::BadNumber(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_line_trims_string_amounts() {
+        let line = r#"{"type":"deposit","client":1,"tx":1,"amount":" 1.5 "}"#;
+        match parse_line(line, true).unwrap().to_command() {
+            Ok(Command::Deposit { amount, .. }) => {
+                assert_eq!(amount, Decimal::from_str("1.5").unwrap())
+            }
+            other => panic!("Expected deposit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_jsonl_reader_positions_rows_after_invalid_utf8() {
+        let mut input = b"{\"type\":\"deposit\",\"client\":1,\"tx\":1,\"amount\":\"1\"}\n".to_vec();
+        let bad_start = input.len() as u64;
+        input.extend_from_slice(b"\xff\xfe\n");
+        let next_start = input.len() as u64;
+        input.extend_from_slice(b"{\"type\":\"deposit\",\"client\":1,\"tx\":2,\"amount\":\"1\"}\n");
+
+        let records: Vec<_> = JsonlReader::new(input.as_slice(), false).collect();
+        assert_eq!(records.len(), 3);
+
+        let error = records[1].as_ref().unwrap_err();
+        assert!(matches!(error.kind, InputErrorKind::Malformed(_)));
+        assert_eq!(
+            error.position,
+            Some(Position {
+                line: 2,
+                byte: bad_start
+            })
+        );
+
+        let (position, _) = records[2].as_ref().unwrap();
+        assert_eq!(
+            *position,
+            Position {
+                line: 3,
+                byte: next_start
+            }
+        );
+    }
+
+    #[test]
+    fn test_jsonl_reader_classifies_bad_fields() {
+        let input = r#"{"type":"deposit","client":70000,"tx":1,"amount":"1.0"}
+{"type":"deposit","client":1,"tx":"7","amount":"1.0"}
+{"type":"deposit","client":1,"tx":2,"amount":"one"}
+{"type":"deposit","client":1,"tx":3,"amount":true}
+{"client":1,"tx":4}
+"#;
+
+        let kinds: Vec<_> = JsonlReader::new(input.as_bytes(), false)
+            .map(|record| record.unwrap_err().kind)
+            .collect();
+
+        assert!(matches!(&kinds[0], InputErrorKind::BadId(msg) if msg == "client: 70000"));
+        assert!(matches!(&kinds[1], InputErrorKind::BadId(msg) if msg == "tx: \"7\""));
+        assert!(matches!(&kinds[2], InputErrorKind::BadNumber(msg) if msg == "amount: one"));
+        assert!(matches!(&kinds[3], InputErrorKind::BadNumber(_)));
+        assert!(matches!(&kinds[4], InputErrorKind::Malformed(msg) if msg.contains("type")));
+    }
+
+    #[test]
+    fn test_jsonl_reader_strict_rejects_unknown_fields() {
+        let input = r#"{"type":"deposit","client":1,"tx":1,"amount":"1.0","memo":"x"}"#;
+
+        let lenient = JsonlReader::new(input.as_bytes(), false).next().unwrap();
+        assert!(lenient.is_ok());
+
+        let strict = JsonlReader::new(input.as_bytes(), true).next().unwrap();
+        assert_eq!(
+            strict.unwrap_err().kind,
+            InputErrorKind::UnknownField("memo".into())
+        );
+    }
+}
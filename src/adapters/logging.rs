@@ -0,0 +1,76 @@
+use std::{io::IsTerminal, str::FromStr};
+
+use tracing_subscriber::EnvFilter;
+
+/// Environment variable holding the log filter when `--log-level` is not given.
+pub const LOG_LEVEL_ENV: &str = "RUST_LOG";
+/// Environment variable holding the log format when `--log-format` is not given.
+pub const LOG_FORMAT_ENV: &str = "PAYMENTS_LOG_FORMAT";
+
+const DEFAULT_LEVEL: &str = "info";
+
+/// How diagnostics are written to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LogFormat {
+    /// One readable line per event.
+    #[default]
+    Human,
+    /// One JSON object per event, including the fields of enclosing spans.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(LogFormat::Human),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format: {}", s)),
+        }
+    }
+}
+
+/// Install the global subscriber writing to stderr.
+/// `level` is an `EnvFilter` directive such as `debug` or `[command{client=42}]=debug`;
+/// command-line values take precedence over the environment.
+pub fn init(level: Option<&str>, format: Option<LogFormat>) -> Result<(), String> {
+    let level = level
+        .map(str::to_string)
+        .or_else(|| std::env::var(LOG_LEVEL_ENV).ok())
+        .unwrap_or_else(|| DEFAULT_LEVEL.to_string());
+    let filter =
+        EnvFilter::try_new(&level).map_err(|e| format!("Invalid log level {}: {}", level, e))?;
+
+    let format = match format {
+        Some(format) => format,
+        None => match std::env::var(LOG_FORMAT_ENV) {
+            Ok(value) => value.parse()?,
+            Err(_) => LogFormat::default(),
+        },
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+
+    match format {
+        LogFormat::Human => builder
+            .with_ansi(std::io::stderr().is_terminal())
+            .try_init(),
+        LogFormat::Json => builder.json().with_current_span(true).try_init(),
+    }
+    .map_err(|e| format!("Failed to initialise logging: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_format_from_str() {
+        assert_eq!("human".parse::<LogFormat>(), Ok(LogFormat::Human));
+        assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+}
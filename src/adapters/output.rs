@@ -1,49 +1,244 @@
 use rust_decimal::Decimal;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::models::account::Account;
+use crate::models::{
+    account::{Account, LockReason},
+    precision::Precision,
+};
 
-/// Helper struct for serializing account output with total.
-#[derive(Serialize)]
-pub struct AccountOutput<'a> {
+/// Helper struct for serializing account output with total, normalized to the output scale.
+/// Also read back from accounts files, ignoring the extra fields of the JSON formats.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountOutput {
     pub client: u16,
 
     #[serde(with = "rust_decimal::serde::str")]
-    pub available: &'a Decimal,
+    pub available: Decimal,
 
     #[serde(with = "rust_decimal::serde::str")]
-    pub held: &'a Decimal,
+    pub held: Decimal,
 
     #[serde(with = "rust_decimal::serde::str")]
-    pub total: &'a Decimal,
+    pub total: Decimal,
 
     pub locked: bool,
 }
 
-use std::{collections::HashMap, io::Write};
+impl AccountOutput {
+    pub fn new(account: &Account, precision: &Precision) -> Self {
+        AccountOutput {
+            client: account.client_id,
+            available: precision.normalize(account.available),
+            held: precision.normalize(account.held),
+            total: precision.normalize(account.total()),
+            locked: account.locked,
+        }
+    }
+}
+
+/// Account output for JSON formats, which also carries the fields the CSV schema has no columns for.
+#[derive(Serialize)]
+pub struct AccountJsonOutput<'a> {
+    #[serde(flatten)]
+    pub balances: AccountOutput,
+
+    pub lock_reason: &'a Option<LockReason>,
+    pub deposit_count: u32,
+    pub withdrawal_count: u32,
+    pub dispute_count: u32,
+}
+
+/// Supported encodings of the final account balances.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    Json,
+    Jsonl,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+}
+
+/// Order of account rows in the output. Every order is total, so output is identical run to run.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SortOrder {
+    /// Ascending client id.
+    #[default]
+    Client,
+    /// Descending total balance, ties broken by client id.
+    Total,
+    /// Locked accounts first, then by client id.
+    Locked,
+}
 
-pub fn output_accounts<W: Write>(accounts: &HashMap<u16, Account>, writer: W) {
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client" => Ok(SortOrder::Client),
+            "total" => Ok(SortOrder::Total),
+            "locked" => Ok(SortOrder::Locked),
+            _ => Err(format!("Unknown sort order: {}", s)),
+        }
+    }
+}
+
+/// How final account balances are written.
+#[derive(Debug, Clone, Default)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    pub sort: SortOrder,
+    /// Balances are written with exactly this many decimal places.
+    pub precision: Precision,
+}
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Write accounts in the requested format and order.
+/// All output goes through here, so callers never depend on `HashMap` iteration order.
+pub fn write_accounts<W: Write>(
+    accounts: &HashMap<u16, Account>,
+    options: &OutputOptions,
+    writer: W,
+) -> io::Result<()> {
+    let sorted = sort_accounts(accounts, options.sort);
+
+    match options.format {
+        OutputFormat::Csv => output_accounts(&sorted, &options.precision, writer),
+        OutputFormat::Json => output_accounts_json(&sorted, &options.precision, writer),
+        OutputFormat::Jsonl => output_accounts_jsonl(&sorted, &options.precision, writer),
+    }
+}
+
+/// Write accounts to the file at `path`, replacing it atomically.
+pub fn write_accounts_to_file(
+    accounts: &HashMap<u16, Account>,
+    options: &OutputOptions,
+    path: &str,
+) -> io::Result<()> {
+    write_atomic(path, |writer| write_accounts(accounts, options, writer))
+}
+
+/// Write a file through a temporary sibling that is renamed over `path` only once everything
+/// has been written and synced. On failure the temporary file is removed, so `path` never
+/// holds partial content.
+pub fn write_atomic<F>(path: &str, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    let temp = temp_path(path);
+    let result = File::create(&temp)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()
+        })
+        .and_then(|()| fs::rename(&temp, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Hidden file next to `path`, so the rename stays within one filesystem.
+fn temp_path(path: &str) -> PathBuf {
+    let path = Path::new(path);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.tmp", name))
+}
+
+/// Collect accounts in the given order.
+pub fn sort_accounts(accounts: &HashMap<u16, Account>, order: SortOrder) -> Vec<&Account> {
+    let mut sorted: Vec<&Account> = accounts.values().collect();
+
+    match order {
+        SortOrder::Client => sorted.sort_by_key(|acc| acc.client_id),
+        SortOrder::Total => sorted.sort_by_key(|acc| (Reverse(acc.total()), acc.client_id)),
+        SortOrder::Locked => sorted.sort_by_key(|acc| (!acc.locked, acc.client_id)),
+    }
+
+    sorted
+}
+
+pub fn output_accounts<W: Write>(
+    accounts: &[&Account],
+    precision: &Precision,
+    writer: W,
+) -> io::Result<()> {
     let mut builder = csv::WriterBuilder::new()
         .has_headers(false)
         .from_writer(writer);
 
-    let _ = builder.write_record(["client", "available", "held", "total", "locked"]);
+    builder.write_record(["client", "available", "held", "total", "locked"])?;
 
-    for account in accounts.values() {
-        let total = account.available + account.held;
+    for account in accounts {
+        builder.serialize(AccountOutput::new(account, precision))?;
+    }
 
-        let output = AccountOutput {
-            client: account.client_id,
-            available: &account.available,
-            held: &account.held,
-            total: &total,
-            locked: account.locked,
-        };
+    builder.flush()
+}
 
-        let _ = builder.serialize(&output);
+pub fn json_output<'a>(account: &'a Account, precision: &Precision) -> AccountJsonOutput<'a> {
+    AccountJsonOutput {
+        balances: AccountOutput::new(account, precision),
+        lock_reason: &account.lock_reason,
+        deposit_count: account.deposit_count,
+        withdrawal_count: account.withdrawal_count,
+        dispute_count: account.dispute_count,
     }
+}
+
+/// Write all accounts as a single JSON array.
+pub fn output_accounts_json<W: Write>(
+    accounts: &[&Account],
+    precision: &Precision,
+    mut writer: W,
+) -> io::Result<()> {
+    let output: Vec<AccountJsonOutput> = accounts
+        .iter()
+        .map(|account| json_output(account, precision))
+        .collect();
 
-    let _ = builder.flush();
+    serde_json::to_writer_pretty(&mut writer, &output)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+/// Write one JSON object per account, one per line.
+pub fn output_accounts_jsonl<W: Write>(
+    accounts: &[&Account],
+    precision: &Precision,
+    writer: W,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+
+    for account in accounts {
+        serde_json::to_writer(&mut writer, &json_output(account, precision))?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()
 }
 
 #[cfg(test)]
@@ -60,34 +255,137 @@ mod tests {
         accounts.insert(
             1,
             Account {
-                client_id: 1,
                 available: Decimal::from_str("10.5").unwrap(),
                 held: Decimal::from_str("2.5").unwrap(),
-                locked: false,
+                ..Account::new(1)
             },
         );
 
         accounts.insert(
             2,
             Account {
-                client_id: 2,
                 available: Decimal::from_str("3.0").unwrap(),
-                held: Decimal::ZERO,
                 locked: true,
+                lock_reason: Some(LockReason::Chargeback { tx: 7 }),
+                ..Account::new(2)
             },
         );
 
         let mut output = Vec::new();
 
-        output_accounts(&accounts, &mut output);
+        write_accounts(&accounts, &OutputOptions::default(), &mut output).unwrap();
 
         let csv_str = str::from_utf8(&output).unwrap();
 
         println!("CSV Output:\n{}", csv_str);
 
-        // Assert it contains expected rows
-        assert!(csv_str.contains("client,available,held,total,locked"));
-        assert!(csv_str.contains("1,10.5,2.5,13.0,false"));
-        assert!(csv_str.contains("2,3.0,0,3.0,true"));
+        // Assert it contains expected rows, in client order
+        assert_eq!(
+            csv_str,
+            "client,available,held,total,locked\n\
+             1,10.5000,2.5000,13.0000,false\n\
+             2,3.0000,0.0000,3.0000,true\n"
+        );
+    }
+
+    #[test]
+    fn test_output_accounts_json_formats() {
+        let mut accounts = HashMap::new();
+
+        accounts.insert(
+            3,
+            Account {
+                available: Decimal::from_str("-1.5").unwrap(),
+                held: Decimal::from_str("0.5").unwrap(),
+                locked: true,
+                lock_reason: Some(LockReason::Chargeback { tx: 9 }),
+                deposit_count: 2,
+                withdrawal_count: 1,
+                dispute_count: 1,
+                ..Account::new(3)
+            },
+        );
+
+        let mut output = Vec::new();
+        let options = OutputOptions {
+            format: OutputFormat::Json,
+            ..OutputOptions::default()
+        };
+        write_accounts(&accounts, &options, &mut output).unwrap();
+
+        let parsed: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            parsed,
+            serde_json::json!([{
+                "client": 3,
+                "available": "-1.5000",
+                "held": "0.5000",
+                "total": "-1.0000",
+                "locked": true,
+                "lock_reason": { "reason": "chargeback", "tx": 9 },
+                "deposit_count": 2,
+                "withdrawal_count": 1,
+                "dispute_count": 1,
+            }])
+        );
+
+        let mut output = Vec::new();
+        let options = OutputOptions {
+            format: OutputFormat::Jsonl,
+            ..OutputOptions::default()
+        };
+        write_accounts(&accounts, &options, &mut output).unwrap();
+
+        let jsonl_str = str::from_utf8(&output).unwrap();
+        assert_eq!(jsonl_str.lines().count(), 1);
+        let line: serde_json::Value = serde_json::from_str(jsonl_str.trim()).unwrap();
+        assert_eq!(line, parsed[0]);
+    }
+
+    #[test]
+    fn test_sort_accounts_orders() {
+        let mut accounts = HashMap::new();
+        for (id, available, locked) in [(3, "5.0", false), (1, "5.0", true), (2, "9.0", false)] {
+            accounts.insert(
+                id,
+                Account {
+                    available: Decimal::from_str(available).unwrap(),
+                    locked,
+                    ..Account::new(id)
+                },
+            );
+        }
+
+        let ids = |order| -> Vec<u16> {
+            sort_accounts(&accounts, order)
+                .iter()
+                .map(|acc| acc.client_id)
+                .collect()
+        };
+
+        assert_eq!(ids(SortOrder::Client), vec![1, 2, 3]);
+        assert_eq!(ids(SortOrder::Total), vec![2, 1, 3]);
+        assert_eq!(ids(SortOrder::Locked), vec![1, 2, 3]);
+        assert_eq!("total".parse(), Ok(SortOrder::Total));
+        assert!("random".parse::<SortOrder>().is_err());
+    }
+
+    #[test]
+    fn test_write_atomic_never_leaves_partial_output() {
+        let path = std::env::temp_dir().join("payments_engine_atomic_output.csv");
+        let path = path.to_str().unwrap();
+
+        write_atomic(path, |writer| writer.write_all(b"first\n")).unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), "first\n");
+
+        let failed = write_atomic(path, |writer| {
+            writer.write_all(b"partial")?;
+            Err(io::Error::other("disk full"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read_to_string(path).unwrap(), "first\n");
+        assert!(!temp_path(path).exists());
+
+        fs::remove_file(path).unwrap();
     }
 }
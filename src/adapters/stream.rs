@@ -0,0 +1,159 @@
+use std::{future::Future, io};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    task::JoinSet,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    adapters::{csv_parser, jsonl_parser},
+    engine::runner::{SubmitOutcome, Submitter},
+};
+
+/// Where the line-protocol listener accepts connections.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamAddr {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
+
+/// Accept connections until Ctrl-C, applying every line received in the engine and
+/// acknowledging it on the connection it came from.
+pub async fn serve_lines(addr: &StreamAddr, submitter: Submitter) -> io::Result<()> {
+    match addr {
+        StreamAddr::Tcp(addr) => {
+            let listener = TcpListener::bind(addr).await?;
+            info!("Accepting transactions on tcp://{}", listener.local_addr()?);
+            accept_until_shutdown(|| async { Ok(listener.accept().await?.0) }, submitter).await;
+        }
+        #[cfg(unix)]
+        StreamAddr::Unix(path) => {
+            let listener = tokio::net::UnixListener::bind(path)?;
+            info!("Accepting transactions on unix://{}", path.display());
+            accept_until_shutdown(|| async { Ok(listener.accept().await?.0) }, submitter).await;
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Run a connection handler for every accepted stream, and drop all of them on Ctrl-C
+/// so their engine senders are released.
+async fn accept_until_shutdown<S, F, Fut>(mut accept: F, submitter: Submitter)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = io::Result<S>>,
+{
+    let mut connections = JoinSet::new();
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            accepted = accept() => match accepted {
+                Ok(stream) => {
+                    connections.spawn(handle_connection(stream, submitter.clone()));
+                }
+                Err(e) => warn!("Failed to accept connection: {}", e),
+            },
+            Some(Ok(Err(e))) = connections.join_next(), if !connections.is_empty() => {
+                warn!("Connection closed with error: {}", e);
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+
+    connections.abort_all();
+}
+
+/// Read newline-delimited transactions and write one acknowledgement line per input line:
+/// `ok`, `rejected <reason>` or `invalid <reason> <message>`.
+/// Lines starting with `{` are read as JSON objects, anything else as a CSV row without header.
+pub async fn handle_connection<S>(stream: S, submitter: Submitter) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let parsed = match line.starts_with('{') {
+            true => jsonl_parser::parse_line(line, true),
+            false => csv_parser::parse_line(line),
+        };
+        let outcome = match parsed {
+            Ok(input) => submitter.submit(&input).await,
+            Err(kind) => SubmitOutcome::Invalid(kind),
+        };
+
+        let ack = match outcome {
+            SubmitOutcome::Applied => "ok".to_string(),
+            SubmitOutcome::Rejected(rejection) => format!("rejected {}", rejection),
+            SubmitOutcome::Invalid(kind) => format!("invalid {} {}", kind.as_str(), kind),
+            SubmitOutcome::Stopped => {
+                writer.write_all(b"error engine stopped\n").await?;
+                break;
+            }
+        };
+        debug!(line, ack, "acknowledged");
+
+        writer.write_all(ack.as_bytes()).await?;
+        writer.write_all(b"\n").await?;
+    }
+
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{engine::runner, models::precision::Precision};
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_connection_acknowledges_every_line() {
+        let (cmd_tx, _engine) = runner::setup_engine(Default::default());
+        let submitter = Submitter::new(cmd_tx, Precision::default());
+        let (mut client, server) = tokio::io::duplex(1024);
+        let handler = tokio::spawn(handle_connection(server, submitter));
+
+        client
+            .write_all(
+                b"deposit,1,1,10.0\n\
+                  {\"type\":\"withdrawal\",\"client\":1,\"tx\":2,\"amount\":20}\n\
+                  \n\
+                  deposit,x,3,1.0\n\
+                  dispute,1,1,5.0\n\
+                  dispute,1,1\n",
+            )
+            .await
+            .unwrap();
+        client.shutdown().await.unwrap();
+
+        let mut acks = String::new();
+        client.read_to_string(&mut acks).await.unwrap();
+        handler.await.unwrap().unwrap();
+
+        let acks: Vec<&str> = acks.lines().collect();
+        assert_eq!(
+            acks,
+            [
+                "ok",
+                "rejected insufficient_funds",
+                "invalid bad_id Invalid id: client: x",
+                "invalid unexpected_amount Unexpected amount in dispute",
+                "ok",
+            ]
+        );
+    }
+}
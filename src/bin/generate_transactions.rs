@@ -0,0 +1,305 @@
+//! Generate synthetic transaction CSVs for load testing the payments engine.
+
+use clap::Parser;
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::IndexedRandom};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+/// Exit status when the output cannot be written, as for the engine itself.
+const EXIT_IO: i32 = 3;
+
+/// Largest generated deposit, in ten-thousandths.
+const MAX_DEPOSIT: i64 = 10_000_000;
+
+/// Share of withdrawals asking for more than the client has.
+const OVERDRAW_RATE: f64 = 0.05;
+
+fn rate(value: &str) -> Result<f64, String> {
+    let rate: f64 = value.parse().map_err(|e| format!("{}", e))?;
+    match (0.0..=1.0).contains(&rate) {
+        true => Ok(rate),
+        false => Err(format!("{} is not between 0 and 1", rate)),
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
+#[command(
+    name = "generate_transactions",
+    about = "Generate a synthetic transactions CSV for the payments engine"
+)]
+struct Options {
+    /// Number of distinct clients, numbered from 1
+    #[arg(long, default_value_t = 1000, value_parser = clap::value_parser!(u16).range(1..))]
+    clients: u16,
+    /// Number of rows to write, not counting the header; fewer than 2^32 - 1, so every
+    /// deposit and withdrawal gets its own u32 tx id
+    #[arg(long, default_value_t = 10_000, value_parser = clap::value_parser!(u64).range(..u64::from(u32::MAX)))]
+    rows: u64,
+    /// Share of deposits and withdrawals that are withdrawals
+    #[arg(long, default_value_t = 0.4, value_parser = rate)]
+    withdrawal_rate: f64,
+    /// Share of rows that dispute an earlier deposit
+    #[arg(long, default_value_t = 0.01, value_parser = rate)]
+    dispute_rate: f64,
+    /// Share of disputes settled by a chargeback rather than a resolve; a charged back client
+    /// gets no further rows, except that the last open client is never charged back
+    #[arg(long, default_value_t = 0.01, value_parser = rate)]
+    chargeback_rate: f64,
+    /// Share of rows that reuse the id of an earlier deposit or withdrawal
+    #[arg(long, default_value_t = 0.0, value_parser = rate)]
+    duplicate_rate: f64,
+    /// Share of rows that are malformed
+    #[arg(long, default_value_t = 0.0, value_parser = rate)]
+    malformed_rate: f64,
+    /// Seed for the random generator; the same seed writes the same file
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Write to this file instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<String>,
+}
+
+/// Keeps enough of each client's history to write rows the engine mostly accepts.
+struct Generator {
+    options: Options,
+    rng: StdRng,
+    next_tx: u32,
+    /// Clients not locked by a chargeback, which all new rows go to.
+    open_clients: Vec<u16>,
+    locked: Vec<bool>,
+    /// Expected available balance of each client, in ten-thousandths.
+    balances: Vec<i64>,
+    /// Deposits that can still be disputed, as (client, tx, amount).
+    deposits: Vec<(u16, u32, i64)>,
+    /// Disputes waiting for a resolve or chargeback.
+    disputes: Vec<(u16, u32, i64)>,
+}
+
+impl Generator {
+    fn new(options: Options) -> Self {
+        Generator {
+            rng: StdRng::seed_from_u64(options.seed),
+            next_tx: 1,
+            open_clients: (1..=options.clients).collect(),
+            locked: vec![false; usize::from(options.clients) + 1],
+            balances: vec![0; usize::from(options.clients) + 1],
+            deposits: Vec::new(),
+            disputes: Vec::new(),
+            options,
+        }
+    }
+
+    fn write<W: Write>(&mut self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "type,client,tx,amount")?;
+        for _ in 0..self.options.rows {
+            let row = self.next_row();
+            writeln!(writer, "{}", row)?;
+        }
+        writer.flush()
+    }
+
+    fn next_row(&mut self) -> String {
+        if self.rng.random_bool(self.options.malformed_rate) {
+            return self.malformed();
+        }
+        if self.next_tx > 1 && self.rng.random_bool(self.options.duplicate_rate) {
+            let tx = self.rng.random_range(1..self.next_tx);
+            let client = self.client();
+            return format!(
+                "deposit,{},{},{}",
+                client,
+                tx,
+                amount(self.deposit_amount())
+            );
+        }
+
+        // Settle open disputes about as often as new ones are raised
+        if self.rng.random_bool(self.options.dispute_rate)
+            && let Some((client, tx, held)) = self.take_open(Pending::Dispute)
+        {
+            if self.open_clients.len() > 1 && self.rng.random_bool(self.options.chargeback_rate) {
+                self.locked[usize::from(client)] = true;
+                self.open_clients.retain(|open| *open != client);
+                return format!("chargeback,{},{},", client, tx);
+            }
+            self.balances[usize::from(client)] += held;
+            self.deposits.push((client, tx, held));
+            return format!("resolve,{},{},", client, tx);
+        }
+        if self.rng.random_bool(self.options.dispute_rate)
+            && let Some((client, tx, held)) = self.take_open(Pending::Deposit)
+        {
+            self.balances[usize::from(client)] -= held;
+            self.disputes.push((client, tx, held));
+            return format!("dispute,{},{},", client, tx);
+        }
+
+        let client = self.client();
+        let tx = self.next_tx;
+        self.next_tx += 1;
+        let balance = self.balances[usize::from(client)];
+
+        if balance > 0 && self.rng.random_bool(self.options.withdrawal_rate) {
+            let value = match self.rng.random_bool(OVERDRAW_RATE) {
+                true => balance + self.rng.random_range(1..=MAX_DEPOSIT),
+                false => self.rng.random_range(1..=balance),
+            };
+            if value <= balance {
+                self.balances[usize::from(client)] -= value;
+            }
+            return format!("withdrawal,{},{},{}", client, tx, amount(value));
+        }
+
+        let value = self.deposit_amount();
+        self.balances[usize::from(client)] += value;
+        self.deposits.push((client, tx, value));
+        format!("deposit,{},{},{}", client, tx, amount(value))
+    }
+
+    fn client(&mut self) -> u16 {
+        *self
+            .open_clients
+            .choose(&mut self.rng)
+            .expect("the last open client is never locked")
+    }
+
+    /// Remove a random deposit or dispute of a client that is still open, dropping those of
+    /// locked clients along the way.
+    fn take_open(&mut self, pending: Pending) -> Option<(u16, u32, i64)> {
+        let list = match pending {
+            Pending::Deposit => &mut self.deposits,
+            Pending::Dispute => &mut self.disputes,
+        };
+        while !list.is_empty() {
+            let index = self.rng.random_range(0..list.len());
+            let entry = list.swap_remove(index);
+            if !self.locked[usize::from(entry.0)] {
+                return Some(entry);
+            }
+        }
+        None
+    }
+
+    fn deposit_amount(&mut self) -> i64 {
+        self.rng.random_range(1..=MAX_DEPOSIT)
+    }
+
+    /// A row the engine cannot read, broken in one of the ways real feeds are.
+    fn malformed(&mut self) -> String {
+        let client = self.client();
+        let tx = self.rng.random_range(1..=u32::MAX);
+        let rows = [
+            format!("transfer,{},{},1.0", client, tx),
+            format!("deposit,{},{},", client, tx),
+            format!("deposit,client{},{},1.0", client, tx),
+            format!("withdrawal,{},{},-5.0", client, tx),
+            format!("deposit,{},{},1.000001", client, tx),
+            format!("deposit,{},{}", client, tx),
+        ];
+        rows.choose(&mut self.rng)
+            .cloned()
+            .expect("there is always a malformed row to pick")
+    }
+}
+
+enum Pending {
+    Deposit,
+    Dispute,
+}
+
+/// Format ten-thousandths as a decimal amount.
+fn amount(value: i64) -> String {
+    format!("{}.{:04}", value / 10_000, value % 10_000)
+}
+
+fn main() {
+    let options = Options::parse();
+    let output = options.output.clone();
+    let mut generator = Generator::new(options);
+
+    let written = match output {
+        Some(path) => File::create(&path).and_then(|file| generator.write(BufWriter::new(file))),
+        None => generator.write(BufWriter::new(io::stdout().lock())),
+    };
+    if let Err(e) = written {
+        eprintln!("Failed to write transactions: {}", e);
+        std::process::exit(EXIT_IO);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate(args: &[&str]) -> String {
+        let options = Options::parse_from(
+            std::iter::once("generate_transactions").chain(args.iter().copied()),
+        );
+        let mut out = Vec::new();
+        Generator::new(options).write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_same_seed_writes_the_same_rows() {
+        let first = generate(&["--rows", "500", "--seed", "7", "--malformed-rate", "0.1"]);
+        assert_eq!(
+            first,
+            generate(&["--rows", "500", "--seed", "7", "--malformed-rate", "0.1"])
+        );
+        assert_ne!(
+            first,
+            generate(&["--rows", "500", "--seed", "8", "--malformed-rate", "0.1"])
+        );
+        assert_eq!(first.lines().count(), 501);
+    }
+
+    #[test]
+    fn test_rows_follow_the_requested_mix() {
+        let out = generate(&[
+            "--rows",
+            "20000",
+            "--clients",
+            "5",
+            "--dispute-rate",
+            "0.1",
+            "--chargeback-rate",
+            "0.5",
+        ]);
+        let count = |kind: &str| out.lines().filter(|line| line.starts_with(kind)).count();
+
+        assert!(count("deposit,") > count("withdrawal,"));
+        assert!(count("dispute,") > 1000);
+        assert!(count("resolve,") > 0 && count("chargeback,") > 0);
+
+        // Charged back clients get no further rows, and one client always stays open
+        let mut locked = std::collections::HashSet::new();
+        for line in out.lines().skip(1) {
+            let client: u16 = line.split(',').nth(1).unwrap().parse().unwrap();
+            assert!((1..=5).contains(&client));
+            assert!(!locked.contains(&client), "row for locked client: {}", line);
+            if line.starts_with("chargeback,") {
+                locked.insert(client);
+            }
+        }
+        assert!(locked.len() < 5);
+    }
+
+    #[test]
+    fn test_rows_fit_the_tx_id_space() {
+        let parse = |rows: u64| {
+            Options::try_parse_from(["generate_transactions", "--rows", &rows.to_string()])
+        };
+        assert!(parse(u64::from(u32::MAX) - 1).is_ok());
+        assert!(parse(u64::from(u32::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_rate_must_be_a_share() {
+        assert_eq!(rate("0.25"), Ok(0.25));
+        assert!(rate("1.5").is_err());
+        assert!(rate("many").is_err());
+    }
+}
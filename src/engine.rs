@@ -1,2 +1,13 @@
+pub mod checkpoint;
+pub mod diff;
+pub mod journal;
+pub mod metrics;
+pub mod policy;
+pub mod reconcile;
+pub mod rejection;
 pub mod runner;
 pub mod state;
+pub mod statement;
+pub mod stats;
+pub mod validation;
+pub mod whatif;
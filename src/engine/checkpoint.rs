@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+};
+
+use crate::{adapters::output::write_atomic, engine::state::State, models::error::Position};
+
+/// Serialized form of a checkpoint when writing, borrowing the engine state.
+#[derive(Serialize)]
+struct CheckpointRef<'a> {
+    line: u64,
+    byte: u64,
+    state: &'a State,
+}
+
+/// Engine state after the input row starting at `line` and `byte`, to resume following from.
+#[derive(Debug, Deserialize)]
+pub struct Checkpoint {
+    pub line: u64,
+    pub byte: u64,
+    pub state: State,
+}
+
+impl Checkpoint {
+    /// Position of the last row reflected in the state.
+    pub fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            byte: self.byte,
+        }
+    }
+}
+
+/// Replace the checkpoint at `path` atomically, so a crash never leaves a truncated one behind.
+pub fn write_checkpoint(path: &str, position: Position, state: &State) -> io::Result<()> {
+    let checkpoint = CheckpointRef {
+        line: position.line,
+        byte: position.byte,
+        state,
+    };
+    write_atomic(path, |writer| {
+        Ok(serde_json::to_writer(writer, &checkpoint)?)
+    })
+}
+
+/// Read the checkpoint at `path`, if one has been written.
+pub fn read_checkpoint(path: &str) -> io::Result<Option<Checkpoint>> {
+    if !Path::new(path).exists() {
+        return Ok(None);
+    }
+
+    let reader = BufReader::new(File::open(path)?);
+    let checkpoint = serde_json::from_reader(reader)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Some(checkpoint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::command::Command;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let path = std::env::temp_dir().join("payments_engine_checkpoint_test.json");
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        assert!(read_checkpoint(path).unwrap().is_none());
+
+        let mut state = State::new();
+        state.process_single_command(Command::Deposit {
+            client_id: 4,
+            tx: 1,
+            amount: Decimal::TEN,
+        });
+        write_checkpoint(path, Position { line: 7, byte: 120 }, &state).unwrap();
+
+        let checkpoint = read_checkpoint(path).unwrap().unwrap();
+        assert_eq!(checkpoint.position(), Position { line: 7, byte: 120 });
+        assert_eq!(checkpoint.state.accounts[&4].available, Decimal::TEN);
+    }
+}
@@ -0,0 +1,302 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use crate::adapters::{accounts_file::Accounts, output::AccountOutput};
+
+/// How a diff is written.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DiffFormat {
+    /// Grouped, readable summary.
+    #[default]
+    Human,
+    /// One row per changed client.
+    Csv,
+}
+
+impl FromStr for DiffFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(DiffFormat::Human),
+            "csv" => Ok(DiffFormat::Csv),
+            _ => Err(format!("Unknown diff format: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    New,
+    Removed,
+    Changed,
+}
+
+/// How one client's account moved between two snapshots. A new client moves from zero and a
+/// removed one to zero; its missing lock state is left empty.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AccountDiff {
+    pub client: u16,
+    pub change: Change,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub available_delta: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub held_delta: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub total_delta: Decimal,
+    pub locked_before: Option<bool>,
+    pub locked_after: Option<bool>,
+}
+
+/// Clients that differ between two snapshots, in ascending client order, and the net change
+/// over all of them.
+#[derive(Debug, Default, PartialEq)]
+pub struct DiffReport {
+    pub accounts: Vec<AccountDiff>,
+    pub net_available: Decimal,
+    pub net_held: Decimal,
+    pub net_total: Decimal,
+}
+
+/// A change too large for a decimal, e.g. between balances near opposite limits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffOverflow {
+    /// The client whose change overflowed, or `None` for the net change over all clients.
+    pub client: Option<u16>,
+    pub field: &'static str,
+}
+
+impl fmt::Display for DiffOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.client {
+            Some(client) => write!(f, "Change in {} of client {} overflows", self.field, client),
+            None => write!(f, "Net change in {} overflows", self.field),
+        }
+    }
+}
+
+impl std::error::Error for DiffOverflow {}
+
+/// Compare two snapshots; clients with identical balances and lock state are left out.
+pub fn diff(before: &Accounts, after: &Accounts) -> Result<DiffReport, DiffOverflow> {
+    let mut clients: Vec<u16> = before.keys().chain(after.keys()).copied().collect();
+    clients.sort_unstable();
+    clients.dedup();
+
+    let accounts = clients
+        .into_iter()
+        .filter_map(|client| {
+            let (old, new) = (before.get(&client), after.get(&client));
+            let change = match (old, new) {
+                (None, _) => Change::New,
+                (_, None) => Change::Removed,
+                (Some(old), Some(new)) if old == new => return None,
+                _ => Change::Changed,
+            };
+            let delta = |field: &'static str, amount: fn(&AccountOutput) -> Decimal| {
+                let amounts = [old.map(amount), new.map(amount)];
+                let delta = new
+                    .map_or(Decimal::ZERO, amount)
+                    .checked_sub(old.map_or(Decimal::ZERO, amount))
+                    .ok_or(DiffOverflow {
+                        client: Some(client),
+                        field,
+                    })?;
+                Ok(at_scale(delta, amounts.iter().flatten()))
+            };
+
+            let account = || {
+                Ok(AccountDiff {
+                    client,
+                    change,
+                    available_delta: delta("available", |acc| acc.available)?,
+                    held_delta: delta("held", |acc| acc.held)?,
+                    total_delta: delta("total", |acc| acc.total)?,
+                    locked_before: old.map(|acc| acc.locked),
+                    locked_after: new.map(|acc| acc.locked),
+                })
+            };
+            Some(account())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let net = |field: &'static str, amount: fn(&AccountDiff) -> Decimal| {
+        let amounts: Vec<Decimal> = accounts.iter().map(amount).collect();
+        let sum = amounts
+            .iter()
+            .try_fold(Decimal::ZERO, |sum, amount| sum.checked_add(*amount))
+            .ok_or(DiffOverflow {
+                client: None,
+                field,
+            })?;
+        Ok(at_scale(sum, amounts.iter()))
+    };
+    Ok(DiffReport {
+        net_available: net("available", |acc| acc.available_delta)?,
+        net_held: net("held", |acc| acc.held_delta)?,
+        net_total: net("total", |acc| acc.total_delta)?,
+        accounts,
+    })
+}
+
+/// Keep the widest scale of `amounts`, which rust_decimal drops when a result comes out zero.
+fn at_scale<'a>(mut amount: Decimal, amounts: impl Iterator<Item = &'a Decimal>) -> Decimal {
+    amount.rescale(amounts.map(Decimal::scale).max().unwrap_or(0));
+    amount
+}
+
+impl DiffReport {
+    /// Write one CSV row per changed client, with a header row.
+    pub fn write_csv<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut wtr = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(writer);
+
+        wtr.write_record([
+            "client",
+            "change",
+            "available_delta",
+            "held_delta",
+            "total_delta",
+            "locked_before",
+            "locked_after",
+        ])?;
+        for account in &self.accounts {
+            wtr.serialize(account)?;
+        }
+        wtr.flush()
+    }
+
+    fn count(&self, change: Change) -> usize {
+        self.accounts
+            .iter()
+            .filter(|acc| acc.change == change)
+            .count()
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn signed(amount: Decimal) -> String {
+            match amount > Decimal::ZERO {
+                true => format!("+{}", amount),
+                false => amount.to_string(),
+            }
+        }
+
+        let sections = [
+            (Change::New, "New clients", '+'),
+            (Change::Removed, "Removed clients", '-'),
+            (Change::Changed, "Changed clients", '~'),
+        ];
+        for (change, title, marker) in sections {
+            writeln!(f, "{}: {}", title, self.count(change))?;
+            for account in self.accounts.iter().filter(|acc| acc.change == change) {
+                write!(
+                    f,
+                    "  {} client {}: available {}, held {}, total {}",
+                    marker,
+                    account.client,
+                    signed(account.available_delta),
+                    signed(account.held_delta),
+                    signed(account.total_delta)
+                )?;
+                if let (Some(before), Some(after)) = (account.locked_before, account.locked_after)
+                    && before != after
+                {
+                    write!(f, ", locked {} -> {}", before, after)?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        writeln!(
+            f,
+            "Net change: available {}, held {}, total {}",
+            signed(self.net_available),
+            signed(self.net_held),
+            signed(self.net_total)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::{accounts_file::read_accounts, output::OutputFormat};
+
+    fn accounts(csv: &str) -> Accounts {
+        let csv = format!("client,available,held,total,locked\n{}", csv);
+        read_accounts(csv.as_bytes(), OutputFormat::Csv).unwrap()
+    }
+
+    #[test]
+    fn test_diff_reports_new_removed_and_changed_clients() {
+        let before = accounts(
+            "1,10.0000,0.0000,10.0000,false\n2,5.0000,0.0000,5.0000,false\n3,1.0000,0.0000,1.0000,false\n",
+        );
+        let after = accounts(
+            "1,10.0000,0.0000,10.0000,false\n2,0.0000,0.0000,0.0000,true\n4,2.5000,0.5000,3.0000,false\n",
+        );
+        let report = diff(&before, &after).unwrap();
+
+        let mut csv = Vec::new();
+        report.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "client,change,available_delta,held_delta,total_delta,locked_before,locked_after\n\
+             2,changed,-5.0000,0.0000,-5.0000,false,true\n\
+             3,removed,-1.0000,0.0000,-1.0000,false,\n\
+             4,new,2.5000,0.5000,3.0000,,false\n"
+        );
+
+        assert_eq!(
+            report.to_string(),
+            "New clients: 1\n  \
+             + client 4: available +2.5000, held +0.5000, total +3.0000\n\
+             Removed clients: 1\n  \
+             - client 3: available -1.0000, held 0.0000, total -1.0000\n\
+             Changed clients: 1\n  \
+             ~ client 2: available -5.0000, held 0.0000, total -5.0000, locked false -> true\n\
+             Net change: available -3.5000, held +0.5000, total -3.0000\n"
+        );
+        assert_eq!(diff(&after, &after), Ok(DiffReport::default()));
+    }
+
+    #[test]
+    fn test_diff_reports_overflow_instead_of_panicking() {
+        let before = accounts(&format!("1,{},0,{},false\n", -Decimal::MAX, -Decimal::MAX));
+        let after = accounts(&format!("1,{},0,{},false\n", Decimal::MAX, Decimal::MAX));
+        assert_eq!(
+            diff(&before, &after),
+            Err(DiffOverflow {
+                client: Some(1),
+                field: "available",
+            })
+        );
+
+        let before = accounts("1,0,0,0,false\n2,0,0,0,false\n");
+        let after = accounts(&format!(
+            "1,{},0,{},false\n2,{},0,{},false\n",
+            Decimal::MAX,
+            Decimal::MAX,
+            Decimal::MAX,
+            Decimal::MAX
+        ));
+        let err = diff(&before, &after).unwrap_err();
+        assert_eq!(
+            err,
+            DiffOverflow {
+                client: None,
+                field: "available",
+            }
+        );
+        assert_eq!(err.to_string(), "Net change in available overflows");
+    }
+}
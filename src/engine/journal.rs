@@ -0,0 +1,427 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, Seek, SeekFrom, Write},
+};
+
+use crate::{
+    engine::state::{State, StateChanges},
+    models::command::SequencedCommand,
+};
+
+/// Default number of journaled commands between two `State` checkpoints.
+pub const DEFAULT_CHECKPOINT_EVERY: usize = 10_000;
+
+/// Point in the input stream at which balances are requested.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AsOf {
+    /// After the command read from this input line (the header is line 1).
+    Row(u64),
+    /// After the last command whose timestamp is not later than this (Unix seconds).
+    Timestamp(u64),
+}
+
+impl AsOf {
+    /// Whether a command at this position comes after the requested point.
+    /// Commands without a timestamp never end a timestamp query on their own.
+    fn is_past(&self, row: u64, timestamp: Option<u64>) -> bool {
+        match self {
+            AsOf::Row(n) => row > *n,
+            AsOf::Timestamp(t) => timestamp.is_some_and(|ts| ts > *t),
+        }
+    }
+}
+
+/// Serialized form of a journal line when writing, borrowing from the engine.
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum JournalRecordRef<'a> {
+    Command(&'a SequencedCommand),
+}
+
+/// One line of a journal file: a processed command.
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JournalRecord {
+    Command(SequencedCommand),
+}
+
+/// One line of a checkpoints file: the state changes up to the command on `row`, and the
+/// journal byte offset right after that command.
+#[derive(Serialize, Deserialize)]
+struct CheckpointLine {
+    row: u64,
+    /// Latest timestamp of any command up to here, which a timestamp query must not be past.
+    timestamp: Option<u64>,
+    offset: u64,
+    changes: StateChanges,
+}
+
+/// Path of the file holding the checkpoints of the journal at `path`.
+pub fn checkpoints_path(path: &str) -> String {
+    format!("{}.checkpoints", path)
+}
+
+/// Writes every command fed to the engine as JSON Lines, and every `checkpoint_every`
+/// commands a checkpoint to a separate writer. A checkpoint holds only the accounts and
+/// transactions touched since the previous one, so both files grow with the input.
+pub struct JournalWriter<W: Write> {
+    writer: W,
+    checkpoints: W,
+    checkpoint_every: usize,
+    since_checkpoint: usize,
+    /// Bytes written to the journal, including those kept from an earlier run.
+    offset: u64,
+    latest_timestamp: Option<u64>,
+    clients: BTreeSet<u16>,
+    txs: BTreeSet<u32>,
+    /// Whether the next checkpoint has to hold the whole state.
+    full: bool,
+}
+
+impl<W: Write> JournalWriter<W> {
+    pub fn new(writer: W, checkpoints: W, checkpoint_every: usize) -> Self {
+        JournalWriter {
+            writer,
+            checkpoints,
+            checkpoint_every: checkpoint_every.max(1),
+            since_checkpoint: 0,
+            offset: 0,
+            latest_timestamp: None,
+            clients: BTreeSet::new(),
+            txs: BTreeSet::new(),
+            full: false,
+        }
+    }
+
+    /// Continue a journal whose first `offset` bytes were written by an earlier run, with
+    /// commands up to `latest_timestamp`. The commands since its last checkpoint are not
+    /// known here, so the next checkpoint holds the whole state.
+    pub fn resuming(self, offset: u64, latest_timestamp: Option<u64>) -> Self {
+        JournalWriter {
+            offset,
+            latest_timestamp,
+            full: true,
+            ..self
+        }
+    }
+
+    /// Append a command that has just been applied to `state`.
+    pub fn record(&mut self, cmd: &SequencedCommand, state: &State) -> io::Result<()> {
+        let mut line = serde_json::to_vec(&JournalRecordRef::Command(cmd))?;
+        line.push(b'\n');
+        self.writer.write_all(&line)?;
+        self.offset += line.len() as u64;
+        self.latest_timestamp = self.latest_timestamp.max(cmd.timestamp);
+
+        // A command only ever changes its own client's account and its own transaction id
+        self.clients.insert(cmd.command.client_id());
+        self.txs.insert(cmd.command.tx());
+        self.since_checkpoint += 1;
+
+        if self.since_checkpoint >= self.checkpoint_every {
+            let changes = match self.full {
+                true => state.all_changes(),
+                false => state.changes(&self.clients, &self.txs),
+            };
+            let checkpoint = CheckpointLine {
+                row: cmd.row,
+                timestamp: self.latest_timestamp,
+                offset: self.offset,
+                changes,
+            };
+            serde_json::to_writer(&mut self.checkpoints, &checkpoint)?;
+            self.checkpoints.write_all(b"\n")?;
+
+            self.clients.clear();
+            self.txs.clear();
+            self.full = false;
+            self.since_checkpoint = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Flush any buffered journal and checkpoint lines.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.checkpoints.flush()
+    }
+}
+
+/// Rebuild the engine state as of the given point. The checkpoints up to it are folded
+/// together, then only the commands journaled after the last of them are read and replayed.
+pub fn state_as_of<R, C>(mut journal: R, checkpoints: C, as_of: AsOf) -> Result<State, String>
+where
+    R: BufRead + Seek,
+    C: BufRead,
+{
+    let mut state = State::new();
+    let mut offset = 0;
+
+    for (index, line) in checkpoints.lines().enumerate() {
+        let line = line.map_err(|e| format!("Failed to read checkpoints: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let checkpoint: CheckpointLine = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid checkpoint line {}: {}", index + 1, e))?;
+        if as_of.is_past(checkpoint.row, checkpoint.timestamp) {
+            break; // checkpoints are in input order, nothing later can qualify
+        }
+        state.apply_changes(checkpoint.changes);
+        offset = checkpoint.offset;
+    }
+
+    journal
+        .seek(SeekFrom::Start(offset))
+        .map_err(|e| format!("Failed to read journal: {}", e))?;
+
+    for cmd in journal_commands(journal) {
+        // line numbers count from where reading started
+        let cmd = cmd.map_err(|e| match offset {
+            0 => e,
+            _ => format!("{}, counting from byte {}", e, offset),
+        })?;
+        if as_of.is_past(cmd.row, cmd.timestamp) {
+            break; // journal is in input order, nothing later can qualify
+        }
+        state.process_single_command(cmd.command);
+    }
+
+    Ok(state)
+}
+
+/// Length in bytes of the leading part of a JSON Lines file that `covered` accepts line by
+/// line. It also ends before a line left unreadable or half-written by a crash.
+fn length_through<R: BufRead>(
+    mut reader: R,
+    mut covered: impl FnMut(&str) -> bool,
+) -> io::Result<u64> {
+    let mut length = 0;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        if read == 0 || !line.ends_with('\n') {
+            break;
+        }
+        if !line.trim().is_empty() && !covered(&line) {
+            break;
+        }
+        length += read as u64;
+    }
+
+    Ok(length)
+}
+
+/// Length in bytes of the leading part of a journal that covers input up to `row`, so a
+/// resumed run can append right after the rows it will not read again, along with the latest
+/// timestamp in that part.
+pub fn journal_length_through<R: BufRead>(reader: R, row: u64) -> io::Result<(u64, Option<u64>)> {
+    let mut latest_timestamp = None;
+    let length = length_through(reader, |line| match serde_json::from_str(line) {
+        Ok(JournalRecord::Command(cmd)) if cmd.row <= row => {
+            latest_timestamp = latest_timestamp.max(cmd.timestamp);
+            true
+        }
+        _ => false,
+    })?;
+    Ok((length, latest_timestamp))
+}
+
+/// Length in bytes of the leading part of a checkpoints file taken up to `row`.
+pub fn checkpoints_length_through<R: BufRead>(reader: R, row: u64) -> io::Result<u64> {
+    length_through(reader, |line| {
+        serde_json::from_str::<CheckpointLine>(line).is_ok_and(|checkpoint| checkpoint.row <= row)
+    })
+}
+
+/// Every command in a journal, in input order.
+pub fn journal_commands<R: BufRead>(
+    reader: R,
+) -> impl Iterator<Item = Result<SequencedCommand, String>> {
+    reader.lines().enumerate().filter_map(|(index, line)| {
+        let line = match line {
+            Ok(line) if line.trim().is_empty() => return None,
+            Ok(line) => line,
+            Err(e) => return Some(Err(format!("Failed to read journal: {}", e))),
+        };
+        match serde_json::from_str(&line) {
+            Ok(JournalRecord::Command(cmd)) => Some(Ok(cmd)),
+            Err(e) => Some(Err(format!("Invalid journal line {}: {}", index + 1, e))),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::command::Command;
+    use rust_decimal::Decimal;
+    use std::{io::Cursor, str::FromStr};
+
+    fn deposit(row: u64, timestamp: Option<u64>, tx: u32, amount: &str) -> SequencedCommand {
+        SequencedCommand {
+            row,
+            byte: 0,
+            timestamp,
+            command: Command::Deposit {
+                client_id: 1,
+                tx,
+                amount: Decimal::from_str(amount).unwrap(),
+            },
+        }
+    }
+
+    /// Run commands through a fresh state while journaling them into memory, returning the
+    /// journal and its checkpoints.
+    fn journal(commands: &[SequencedCommand], checkpoint_every: usize) -> (Vec<u8>, Vec<u8>) {
+        let mut state = State::new();
+        let (mut buffer, mut checkpoints) = (Vec::new(), Vec::new());
+        let mut writer = JournalWriter::new(&mut buffer, &mut checkpoints, checkpoint_every);
+
+        for cmd in commands {
+            state.process_single_command(cmd.command.clone());
+            writer.record(cmd, &state).unwrap();
+        }
+        writer.finish().unwrap();
+
+        (buffer, checkpoints)
+    }
+
+    fn query(journal: &(Vec<u8>, Vec<u8>), as_of: AsOf) -> Result<State, String> {
+        state_as_of(Cursor::new(&journal.0), journal.1.as_slice(), as_of)
+    }
+
+    #[test]
+    fn test_state_as_of_row_uses_checkpoints() {
+        let commands: Vec<_> = (1..=7)
+            .map(|i| deposit(i + 1, None, i as u32, "1.0"))
+            .collect();
+        let journal = journal(&commands, 3);
+
+        let checkpoints: Vec<&str> = std::str::from_utf8(&journal.1).unwrap().lines().collect();
+        assert_eq!(checkpoints.len(), 2);
+        // Each checkpoint holds only the transactions since the previous one
+        assert_eq!(checkpoints[1].matches("\"tx\":").count(), 3);
+
+        for (row, expected) in [(1, "0"), (2, "1.0"), (4, "3.0"), (6, "5.0"), (100, "7.0")] {
+            let state = query(&journal, AsOf::Row(row)).unwrap();
+            let available = state
+                .accounts
+                .get(&1)
+                .map_or(Decimal::ZERO, |acc| acc.available);
+            assert_eq!(
+                available,
+                Decimal::from_str(expected).unwrap(),
+                "row {}",
+                row
+            );
+        }
+
+        // Deposits folded from checkpoints are still known, so a replayed one is a duplicate
+        let state = query(&journal, AsOf::Row(100)).unwrap();
+        assert!(state.transaction(2).is_some());
+    }
+
+    #[test]
+    fn test_state_as_of_reads_the_journal_after_the_checkpoint_only() {
+        let commands: Vec<_> = (1..=4)
+            .map(|i| deposit(i + 1, None, i as u32, "1.0"))
+            .collect();
+        let (mut buffer, checkpoints) = journal(&commands, 2);
+
+        // Damage the first command, which the checkpoint after row 3 already covers
+        buffer[0] = b'x';
+        let state = state_as_of(Cursor::new(&buffer), checkpoints.as_slice(), AsOf::Row(5));
+        assert_eq!(state.unwrap().accounts[&1].available, Decimal::from(4));
+    }
+
+    #[test]
+    fn test_state_as_of_timestamp() {
+        let commands = vec![
+            deposit(2, Some(100), 1, "1.0"),
+            deposit(3, None, 2, "2.0"),
+            deposit(4, Some(200), 3, "4.0"),
+        ];
+        let journal = journal(&commands, 2);
+
+        let state = query(&journal, AsOf::Timestamp(150)).unwrap();
+        let acc = state.accounts.get(&1).unwrap();
+        assert_eq!(acc.available, Decimal::from_str("3.0").unwrap());
+
+        let state = query(&journal, AsOf::Timestamp(50)).unwrap();
+        assert!(state.accounts.is_empty());
+
+        let rows: Vec<u64> = journal_commands(journal.0.as_slice())
+            .map(|cmd| cmd.unwrap().row)
+            .collect();
+        assert_eq!(rows, [2, 3, 4]);
+    }
+
+    #[test]
+    fn test_resumed_journal_checkpoints_the_whole_state() {
+        let commands: Vec<_> = (1..=5)
+            .map(|i| deposit(i + 1, None, i as u32, "1.0"))
+            .collect();
+        let mut state = State::new();
+        for cmd in &commands[..3] {
+            state.process_single_command(cmd.command.clone());
+        }
+
+        let (mut buffer, mut checkpoints) = (Vec::new(), Vec::new());
+        let mut writer = JournalWriter::new(&mut buffer, &mut checkpoints, 2).resuming(0, None);
+        for cmd in &commands[3..] {
+            state.process_single_command(cmd.command.clone());
+            writer.record(cmd, &state).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let state = state_as_of(Cursor::new(&buffer), checkpoints.as_slice(), AsOf::Row(6));
+        assert_eq!(state.unwrap().accounts[&1].available, Decimal::from(5));
+    }
+
+    #[test]
+    fn test_length_through_row() {
+        let commands: Vec<_> = (1..=4)
+            .map(|i| deposit(i + 1, None, i as u32, "1.0"))
+            .collect();
+        let (buffer, checkpoints) = journal(&commands, 2);
+        let lines: Vec<&str> = std::str::from_utf8(&buffer).unwrap().lines().collect();
+        let through =
+            |lines: &[&str]| -> u64 { lines.iter().map(|line| line.len() as u64 + 1).sum() };
+
+        let length = |journal: &[u8], row| journal_length_through(journal, row).unwrap().0;
+
+        assert_eq!(length(&buffer, 3), through(&lines[..2]));
+        assert_eq!(length(&buffer, 100), buffer.len() as u64);
+        assert_eq!(length(&buffer, 1), 0);
+
+        // A last line cut short by a crash is not kept
+        let cut = &buffer[..buffer.len() - 5];
+        assert_eq!(length(cut, 100), through(&lines[..3]));
+
+        // Checkpoints were taken after rows 3 and 5
+        let first = checkpoints.iter().position(|&b| b == b'\n').unwrap() as u64 + 1;
+        assert_eq!(
+            checkpoints_length_through(checkpoints.as_slice(), 4).unwrap(),
+            first
+        );
+        assert_eq!(
+            checkpoints_length_through(checkpoints.as_slice(), 2).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_state_as_of_rejects_corrupt_journal() {
+        let res = state_as_of(Cursor::new("not json\n"), "".as_bytes(), AsOf::Row(1));
+        assert!(res.unwrap_err().starts_with("Invalid journal line 1"));
+
+        let res = state_as_of(Cursor::new(""), "{\"row\":2}\n".as_bytes(), AsOf::Row(3));
+        assert!(res.unwrap_err().starts_with("Invalid checkpoint line 1"));
+    }
+}
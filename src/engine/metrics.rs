@@ -0,0 +1,243 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::engine::{rejection::Rejection, state::State};
+
+/// Upper bounds of the command latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 8] = [
+    0.000_001, 0.000_005, 0.000_01, 0.000_05, 0.000_1, 0.000_5, 0.001, 0.01,
+];
+
+/// Live engine metrics, shared between the engine task and the metrics endpoint.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    commands: Mutex<BTreeMap<(&'static str, &'static str), u64>>,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    latency_count: AtomicU64,
+    latency_sum_nanos: AtomicU64,
+    channel_depth: AtomicUsize,
+    accounts: AtomicUsize,
+    open_disputes: AtomicUsize,
+    transaction_store_bytes: AtomicUsize,
+}
+
+impl Metrics {
+    /// Set the state gauges from the state the engine starts with, which may already hold
+    /// open disputes when restored from a checkpoint.
+    pub fn start(&self, state: &State) {
+        self.accounts.store(state.accounts.len(), Ordering::Relaxed);
+        self.open_disputes
+            .store(state.open_disputes(), Ordering::Relaxed);
+        self.transaction_store_bytes
+            .store(state.transaction_store_bytes(), Ordering::Relaxed);
+    }
+
+    /// Record a command the engine has just processed, and refresh the state gauges.
+    pub fn observe(
+        &self,
+        kind: &'static str,
+        outcome: Result<(), Rejection>,
+        latency: Duration,
+        state: &State,
+        channel_depth: usize,
+    ) {
+        let label = outcome.map_or_else(|r| r.as_str(), |()| "applied");
+        if let Ok(mut commands) = self.commands.lock() {
+            *commands.entry((kind, label)).or_default() += 1;
+        }
+
+        let seconds = latency.as_secs_f64();
+        for (bucket, bound) in self.latency_buckets.iter().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        self.latency_sum_nanos.fetch_add(nanos, Ordering::Relaxed);
+
+        // Only a normal deposit can be disputed and only a disputed one resolved or charged back.
+        // Saturate rather than wrap, should the gauge ever miss a dispute opened earlier.
+        let change = match (kind, outcome) {
+            ("dispute", Ok(())) => |open: usize| Some(open.saturating_add(1)),
+            ("resolve" | "chargeback", Ok(())) => |open: usize| Some(open.saturating_sub(1)),
+            _ => |_| None,
+        };
+        let _ = self
+            .open_disputes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, change);
+
+        self.channel_depth.store(channel_depth, Ordering::Relaxed);
+        self.accounts.store(state.accounts.len(), Ordering::Relaxed);
+        self.transaction_store_bytes
+            .store(state.transaction_store_bytes(), Ordering::Relaxed);
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "payments_commands_total",
+            "counter",
+            "Commands processed by type and outcome.",
+        );
+        if let Ok(commands) = self.commands.lock() {
+            for ((kind, outcome), count) in commands.iter() {
+                let _ = writeln!(
+                    out,
+                    "payments_commands_total{{type=\"{}\",outcome=\"{}\"}} {}",
+                    kind, outcome, count
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "payments_command_latency_seconds",
+            "histogram",
+            "Time to apply a single command.",
+        );
+        for (bucket, bound) in self.latency_buckets.iter().zip(LATENCY_BUCKETS) {
+            let _ = writeln!(
+                out,
+                "payments_command_latency_seconds_bucket{{le=\"{}\"}} {}",
+                bound,
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let count = self.latency_count.load(Ordering::Relaxed);
+        let sum = self.latency_sum_nanos.load(Ordering::Relaxed) as f64 / 1e9;
+        let _ = writeln!(
+            out,
+            "payments_command_latency_seconds_bucket{{le=\"+Inf\"}} {}",
+            count
+        );
+        let _ = writeln!(out, "payments_command_latency_seconds_sum {}", sum);
+        let _ = writeln!(out, "payments_command_latency_seconds_count {}", count);
+
+        let gauges = [
+            (
+                "payments_channel_depth",
+                "Commands waiting in the engine channel.",
+                &self.channel_depth,
+            ),
+            (
+                "payments_accounts",
+                "Client accounts known to the engine.",
+                &self.accounts,
+            ),
+            (
+                "payments_open_disputes",
+                "Transactions currently under dispute.",
+                &self.open_disputes,
+            ),
+            (
+                "payments_transaction_store_bytes",
+                "Approximate memory held by the transaction store.",
+                &self.transaction_store_bytes,
+            ),
+        ];
+        for (name, help, value) in gauges {
+            header(&mut out, name, "gauge", help);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::command::Command;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_render_after_commands() {
+        let metrics = Metrics::default();
+        let mut state = State::new();
+
+        let commands = [
+            Command::Deposit {
+                client_id: 1,
+                tx: 1,
+                amount: Decimal::ONE,
+            },
+            Command::Dispute {
+                client_id: 1,
+                tx: 1,
+            },
+            Command::Dispute {
+                client_id: 1,
+                tx: 2,
+            },
+        ];
+        for cmd in commands {
+            let kind = cmd.kind();
+            let outcome = state.apply_command(cmd);
+            metrics.observe(kind, outcome, Duration::from_micros(2), &state, 7);
+        }
+
+        let text = metrics.render();
+        assert!(text.contains("payments_commands_total{type=\"deposit\",outcome=\"applied\"} 1\n"));
+        assert!(
+            text.contains("payments_commands_total{type=\"dispute\",outcome=\"unknown_tx\"} 1\n")
+        );
+        assert!(text.contains("payments_command_latency_seconds_bucket{le=\"0.000001\"} 0\n"));
+        assert!(text.contains("payments_command_latency_seconds_bucket{le=\"0.000005\"} 3\n"));
+        assert!(text.contains("payments_command_latency_seconds_count 3\n"));
+        assert!(text.contains("payments_channel_depth 7\n"));
+        assert!(text.contains("payments_accounts 1\n"));
+        assert!(text.contains("payments_open_disputes 1\n"));
+        assert!(text.contains("# TYPE payments_transaction_store_bytes gauge\n"));
+    }
+
+    #[test]
+    fn test_open_disputes_start_from_restored_state() {
+        let mut state = State::new();
+        for cmd in [
+            Command::Deposit {
+                client_id: 1,
+                tx: 1,
+                amount: Decimal::ONE,
+            },
+            Command::Dispute {
+                client_id: 1,
+                tx: 1,
+            },
+        ] {
+            state.process_single_command(cmd);
+        }
+
+        let metrics = Metrics::default();
+        metrics.start(&state);
+        assert!(metrics.render().contains("payments_open_disputes 1\n"));
+
+        let cmd = Command::Chargeback {
+            client_id: 1,
+            tx: 1,
+        };
+        let outcome = state.apply_command(cmd);
+        metrics.observe("chargeback", outcome, Duration::ZERO, &state, 0);
+        assert!(metrics.render().contains("payments_open_disputes 0\n"));
+
+        // A gauge that missed the dispute stays at zero instead of wrapping
+        let metrics = Metrics::default();
+        metrics.observe("resolve", Ok(()), Duration::ZERO, &state, 0);
+        assert!(metrics.render().contains("payments_open_disputes 0\n"));
+    }
+}
@@ -0,0 +1,43 @@
+use serde::Deserialize;
+use std::fs;
+
+/// Rules the engine can apply differently, for what-if runs. The default is the engine's
+/// current behaviour.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Policy {
+    /// Let a dispute hold more than the available balance, leaving available negative.
+    pub allow_negative_available: bool,
+    /// Lock the account after a chargeback.
+    pub lock_on_chargeback: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            allow_negative_available: true,
+            lock_on_chargeback: true,
+        }
+    }
+}
+
+/// Read a candidate policy from a TOML file; rules it leaves out keep their current value.
+pub fn load_policy(path: &str) -> Result<Policy, String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read policy {}: {}", path, e))?;
+    toml::from_str(&text).map_err(|e| format!("Invalid policy {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_keeps_current_rules_by_default() {
+        let policy: Policy = toml::from_str("allow-negative-available = false").unwrap();
+        assert!(!policy.allow_negative_available);
+        assert!(policy.lock_on_chargeback);
+
+        assert!(toml::from_str::<Policy>("no-such-rule = true").is_err());
+    }
+}
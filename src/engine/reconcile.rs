@@ -0,0 +1,117 @@
+use serde::Serialize;
+use std::io::{self, Write};
+
+use crate::adapters::{accounts_file::Accounts, output::AccountOutput};
+
+/// One field of a client's account that differs between the expected and computed balances.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Mismatch {
+    pub client: u16,
+    /// `available`, `held`, `total` or `locked`, or `account` when only one side has the client.
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Compare expected balances with computed ones, client by client in ascending order.
+/// Amounts compare by value, so `1.5` in the expected file matches `1.5000`.
+pub fn reconcile(expected: &Accounts, actual: &Accounts) -> Vec<Mismatch> {
+    let mut clients: Vec<u16> = expected.keys().chain(actual.keys()).copied().collect();
+    clients.sort_unstable();
+    clients.dedup();
+
+    let mut mismatches = Vec::new();
+    for client in clients {
+        match (expected.get(&client), actual.get(&client)) {
+            (Some(expected), Some(actual)) => compare(expected, actual, &mut mismatches),
+            (expected, _) => mismatches.push(Mismatch {
+                client,
+                field: "account",
+                expected: presence(expected),
+                actual: presence(actual.get(&client)),
+            }),
+        }
+    }
+    mismatches
+}
+
+fn presence(account: Option<&AccountOutput>) -> String {
+    match account {
+        Some(_) => "present".to_string(),
+        None => "missing".to_string(),
+    }
+}
+
+fn compare(expected: &AccountOutput, actual: &AccountOutput, mismatches: &mut Vec<Mismatch>) {
+    let amounts = [
+        ("available", expected.available, actual.available),
+        ("held", expected.held, actual.held),
+        ("total", expected.total, actual.total),
+    ];
+    for (field, expected_amount, actual_amount) in amounts {
+        if expected_amount != actual_amount {
+            mismatches.push(Mismatch {
+                client: actual.client,
+                field,
+                expected: expected_amount.to_string(),
+                actual: actual_amount.to_string(),
+            });
+        }
+    }
+
+    if expected.locked != actual.locked {
+        mismatches.push(Mismatch {
+            client: actual.client,
+            field: "locked",
+            expected: expected.locked.to_string(),
+            actual: actual.locked.to_string(),
+        });
+    }
+}
+
+/// Write mismatches as CSV with a header row.
+pub fn write_mismatches<W: Write>(mismatches: &[Mismatch], writer: W) -> io::Result<()> {
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(writer);
+
+    wtr.write_record(["client", "field", "expected", "actual"])?;
+    for mismatch in mismatches {
+        wtr.serialize(mismatch)?;
+    }
+    wtr.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::{accounts_file::read_accounts, output::OutputFormat};
+
+    #[test]
+    fn test_reconcile_reports_each_differing_field() {
+        let expected = "client,available,held,total,locked\n\
+                        1,1.5,0,1.5,false\n\
+                        2,5,0,5,false\n\
+                        3,1,0,1,false\n";
+        let actual = "client,available,held,total,locked\n\
+                      1,1.5000,0.0000,1.5000,false\n\
+                      2,3.0000,2.0000,5.0000,true\n\
+                      4,0.0000,0.0000,0.0000,false\n";
+        let expected = read_accounts(expected.as_bytes(), OutputFormat::Csv).unwrap();
+        let actual = read_accounts(actual.as_bytes(), OutputFormat::Csv).unwrap();
+
+        let mismatches = reconcile(&expected, &actual);
+        let mut out = Vec::new();
+        write_mismatches(&mismatches, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "client,field,expected,actual\n\
+             2,available,5,3.0000\n\
+             2,held,0,2.0000\n\
+             2,locked,false,true\n\
+             3,account,present,missing\n\
+             4,account,missing,present\n"
+        );
+        assert!(reconcile(&actual, &actual).is_empty());
+    }
+}
@@ -0,0 +1,47 @@
+use std::fmt;
+
+/// Why the engine refused to apply a command. A rejected command leaves the state untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rejection {
+    /// Deposit or withdrawal amount is zero or negative.
+    NonPositiveAmount,
+    /// Transaction id was already used by an applied deposit or withdrawal.
+    DuplicateTx,
+    /// Account is locked after a chargeback.
+    AccountLocked,
+    /// Withdrawal exceeds the available balance.
+    InsufficientFunds,
+    /// Referenced transaction does not exist or cannot be referenced.
+    UnknownTx,
+    /// Referenced transaction belongs to another client.
+    ClientMismatch,
+    /// Only undisputed deposits can be disputed.
+    NotDisputable,
+    /// Only disputed transactions can be resolved or charged back.
+    NotDisputed,
+    /// A balance would exceed the range of `Decimal`.
+    Overflow,
+}
+
+impl Rejection {
+    /// Stable snake_case identifier, for logs and reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rejection::NonPositiveAmount => "non_positive_amount",
+            Rejection::DuplicateTx => "duplicate_tx",
+            Rejection::AccountLocked => "account_locked",
+            Rejection::InsufficientFunds => "insufficient_funds",
+            Rejection::UnknownTx => "unknown_tx",
+            Rejection::ClientMismatch => "client_mismatch",
+            Rejection::NotDisputable => "not_disputable",
+            Rejection::NotDisputed => "not_disputed",
+            Rejection::Overflow => "overflow",
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
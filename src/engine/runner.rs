@@ -1,90 +1,556 @@
 use crate::{
-    adapters::output::output_accounts,
-    engine::state::State,
-    models::{command::Command, transaction::TransactionInput},
+    adapters::{
+        input::InputRecord,
+        output::{OutputOptions, write_accounts_to_file},
+    },
+    engine::{
+        checkpoint::write_checkpoint,
+        journal::{AsOf, JournalWriter, checkpoints_path, state_as_of},
+        metrics::Metrics,
+        rejection::Rejection,
+        state::State,
+        stats::EngineStats,
+    },
+    models::{
+        command::{Command, SequencedCommand},
+        error::{InputError, InputErrorKind, Position},
+        precision::Precision,
+        transaction::TransactionInput,
+    },
 };
 
-use std::{fs::File, io};
-use tokio::sync::mpsc;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::{Interval, MissedTickBehavior},
+};
+use tracing::{debug, debug_span, error, field, info, warn};
+
+/// Message handled by the engine task, which owns the single `State`.
+pub enum EngineRequest {
+    /// Apply a command, reporting its outcome back when a reply channel is given.
+    Command(
+        SequencedCommand,
+        Option<oneshot::Sender<Result<(), Rejection>>>,
+    ),
+    /// Apply a command that has no input row, such as one received over the network, with
+    /// its timestamp. The engine numbers these in the order it applies them, so the journal
+    /// stays in row order however many clients submit at once.
+    Submit(Command, Option<u64>, oneshot::Sender<Result<(), Rejection>>),
+    /// Run a read-only function against the current state.
+    Query(Box<dyn FnOnce(&State) + Send>),
+}
+
+/// Settings of the engine task, fixed for the duration of a run.
+#[derive(Default)]
+pub struct EngineOptions {
+    /// Journal every processed command, with periodic state checkpoints.
+    pub journal: Option<JournalWriter<BufWriter<File>>>,
+    /// Live metrics to update after every command.
+    pub metrics: Option<Arc<Metrics>>,
+    /// State to start from instead of an empty one, e.g. restored from a checkpoint.
+    pub state: Option<State>,
+    /// Write accounts and a checkpoint periodically while running.
+    pub snapshots: Option<SnapshotOptions>,
+}
+
+/// Periodic outputs of a long-running engine, such as one following a growing file.
+pub struct SnapshotOptions {
+    pub every: Duration,
+    /// Accounts file replaced on every snapshot, and how to write it.
+    pub output: Option<(String, OutputOptions)>,
+    /// Checkpoint file to resume from after a restart.
+    pub checkpoint: Option<String>,
+}
 
-/// Run the engine event loop to receive and handle commands, and then output results.
-pub async fn run(mut rx: mpsc::Receiver<Command>) {
-    let mut state = State::new();
+/// Writes snapshots on a timer, skipping them when nothing was applied since the last one.
+struct Snapshotter {
+    options: SnapshotOptions,
+    interval: Interval,
+    /// Input position of the last command processed.
+    position: Option<Position>,
+    dirty: bool,
+}
+
+impl Snapshotter {
+    fn new(options: SnapshotOptions) -> Self {
+        let mut interval = tokio::time::interval(options.every);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        Snapshotter {
+            options,
+            interval,
+            position: None,
+            dirty: false,
+        }
+    }
+
+    fn record(&mut self, cmd: &SequencedCommand) {
+        self.position = Some(Position {
+            line: cmd.row,
+            byte: cmd.byte,
+        });
+        self.dirty = true;
+    }
+
+    fn write(&mut self, state: &State) {
+        if !self.dirty {
+            return;
+        }
+
+        if let Some((path, output)) = &self.options.output
+            && let Err(e) = write_accounts_to_file(&state.accounts, output, path)
+        {
+            error!("Failed to write accounts snapshot: {}", e);
+        }
+
+        if let (Some(path), Some(position)) = (&self.options.checkpoint, self.position) {
+            match write_checkpoint(path, position, state) {
+                Ok(()) => debug!(line = position.line, "Checkpoint written"),
+                Err(e) => error!("Failed to write checkpoint: {}", e),
+            }
+        }
+
+        self.dirty = false;
+    }
+}
+
+/// Wait for the next snapshot to be due, or forever when snapshots are off.
+async fn next_snapshot(snapshotter: &mut Option<Snapshotter>) {
+    match snapshotter {
+        Some(snapshotter) => {
+            snapshotter.interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Settings for turning input records into commands.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IngestOptions {
+    pub precision: Precision,
+    /// Stop at the first invalid row instead of skipping it.
+    pub strict: bool,
+}
+
+/// What happened to the input rows.
+#[derive(Debug, Default)]
+pub struct IngestSummary {
+    pub processed: usize,
+    pub skipped: usize,
+    pub skipped_by_reason: BTreeMap<&'static str, usize>,
+    /// In strict mode, the invalid row that stopped ingestion.
+    pub failure: Option<InputError>,
+}
+
+impl IngestSummary {
+    fn skip(&mut self, err: &InputError) {
+        self.skipped += 1;
+        *self.skipped_by_reason.entry(err.kind.as_str()).or_default() += 1;
+    }
+}
+
+/// Run the engine event loop to receive and handle commands, and return the final state
+/// along with statistics about what was applied.
+pub async fn run(
+    mut rx: mpsc::Receiver<EngineRequest>,
+    options: EngineOptions,
+) -> (State, EngineStats) {
+    let mut state = options.state.unwrap_or_default();
+    let mut stats = EngineStats::default();
+    let mut journal = options.journal;
+    let metrics = options.metrics;
+    if let Some(metrics) = &metrics {
+        metrics.start(&state);
+    }
+    let mut snapshotter = options.snapshots.map(Snapshotter::new);
+    let mut submitted = 0;
 
     // Process incoming commands
-    while let Some(cmd) = rx.recv().await {
-        state.process_single_command(cmd);
+    loop {
+        let request = tokio::select! {
+            request = rx.recv() => match request {
+                Some(request) => request,
+                None => break,
+            },
+            () = next_snapshot(&mut snapshotter) => {
+                if let Some(snapshotter) = &mut snapshotter {
+                    snapshotter.write(&state);
+                }
+                continue;
+            }
+        };
+
+        let (cmd, reply) = match request {
+            EngineRequest::Command(cmd, reply) => (cmd, reply),
+            EngineRequest::Submit(command, timestamp, reply) => {
+                submitted += 1;
+                let cmd = SequencedCommand {
+                    row: submitted,
+                    byte: 0,
+                    timestamp,
+                    command,
+                };
+                (cmd, Some(reply))
+            }
+            EngineRequest::Query(query) => {
+                query(&state);
+                continue;
+            }
+        };
+
+        let span = debug_span!(
+            "command",
+            client = cmd.command.client_id(),
+            tx = cmd.command.tx(),
+            r#type = cmd.command.kind(),
+            row = cmd.row,
+            outcome = field::Empty,
+        );
+        let _entered = span.enter();
+
+        let started = Instant::now();
+        let outcome = stats.apply(&mut state, cmd.command.clone());
+        match outcome {
+            Ok(()) => span.record("outcome", "applied"),
+            Err(rejection) => span.record("outcome", rejection.as_str()),
+        };
+        if let Some(metrics) = &metrics {
+            let kind = cmd.command.kind();
+            metrics.observe(kind, outcome, started.elapsed(), &state, rx.len());
+        }
+        debug!("command processed");
+
+        if let Some(Err(e)) = journal.as_mut().map(|writer| writer.record(&cmd, &state)) {
+            error!("Failed to write journal, disabling it: {}", e);
+            journal = None;
+        }
+
+        if let Some(snapshotter) = &mut snapshotter {
+            snapshotter.record(&cmd);
+        }
+
+        if let Some(reply) = reply {
+            let _ = reply.send(outcome); // the requester may have gone away
+        }
+    }
+
+    if let Some(snapshotter) = &mut snapshotter {
+        snapshotter.write(&state);
     }
 
-    // All commands processed, output final state of accounts as CSV
-    output_accounts(&state.accounts, io::stdout());
+    if let Some(Err(e)) = journal.map(JournalWriter::finish) {
+        error!("Failed to flush journal: {}", e);
+    }
+
+    // All commands processed
+    (state, stats)
 }
 
 /// Set up engine task and return its handle along with command sender
-pub fn setup_engine() -> (mpsc::Sender<Command>, tokio::task::JoinHandle<()>) {
+pub fn setup_engine(
+    options: EngineOptions,
+) -> (
+    mpsc::Sender<EngineRequest>,
+    tokio::task::JoinHandle<(State, EngineStats)>,
+) {
     let (cmd_tx, cmd_rx) = mpsc::channel(1000);
 
-    let handle = tokio::spawn(async move {
-        run(cmd_rx).await;
-    });
+    let handle = tokio::spawn(async move { run(cmd_rx, options).await });
 
     (cmd_tx, handle)
 }
 
-/// Read CSV, parse to commands, and send to engine
-pub async fn send_commands_to_engine(
-    csv_reader: &mut csv::Reader<File>,
-    cmd_tx: mpsc::Sender<Command>,
-) {
-    let deserialize_iter = csv_reader.deserialize::<TransactionInput>();
-    let mut record_count: usize = 0;
-    let mut skipped_count = 0;
+/// Convert parsed input records to commands and send them to the engine
+pub async fn send_commands_to_engine<I>(
+    records: I,
+    cmd_tx: mpsc::Sender<EngineRequest>,
+    options: IngestOptions,
+) -> IngestSummary
+where
+    I: Iterator<Item = InputRecord>,
+{
+    let mut summary = IngestSummary::default();
 
-    for result in deserialize_iter {
+    for result in records {
         match result {
-            Ok(rec) => {
-                let input: TransactionInput = rec;
-
-                let cmd = match input.to_command() {
+            Ok((position, input)) => {
+                let cmd = match input.to_command_with_precision(&options.precision) {
                     Ok(cmd) => cmd,
-                    Err(err) => {
-                        eprintln!("Skipping invalid command conversion: {}", err);
-                        skipped_count += 1;
+                    Err(kind) => {
+                        let err = InputError::new(kind, Some(position));
+                        if options.strict {
+                            summary.failure = Some(err);
+                            break;
+                        }
+                        warn!(
+                            reason = err.kind.as_str(),
+                            "Skipping invalid command conversion: {}", err
+                        );
+                        summary.skip(&err);
                         continue;
                     }
                 };
 
-                if cmd_tx.send(cmd).await.is_err() {
+                if let Some(kind) = input.unexpected_amount() {
+                    let err = InputError::new(kind, Some(position));
+                    if options.strict {
+                        summary.failure = Some(err);
+                        break;
+                    }
+                    warn!(reason = err.kind.as_str(), "Ignoring amount: {}", err);
+                }
+
+                let sequenced = SequencedCommand {
+                    row: position.line,
+                    byte: position.byte,
+                    timestamp: input.timestamp,
+                    command: cmd,
+                };
+
+                let request = EngineRequest::Command(sequenced, None);
+                if cmd_tx.send(request).await.is_err() {
                     break;
                 }
 
-                record_count += 1;
+                summary.processed += 1;
 
-                if record_count % 1000 == 0 {
+                if summary.processed.is_multiple_of(1000) {
                     tokio::task::yield_now().await;
                 }
             }
             Err(e) => {
-                eprintln!("Skipping invalid CSV line: {}", e);
-                skipped_count += 1;
+                if options.strict {
+                    summary.failure = Some(e);
+                    break;
+                }
+                warn!(
+                    reason = e.kind.as_str(),
+                    "Skipping invalid input line: {}", e
+                );
+                summary.skip(&e);
                 continue;
             }
         }
     }
 
-    eprintln!(
+    info!(
+        processed = summary.processed,
+        skipped = summary.skipped,
         "Processed {} records, skipped {} invalid lines.",
-        record_count, skipped_count
+        summary.processed,
+        summary.skipped
     );
 
     // Close the channel to signal engine no more commands will arrive
     drop(cmd_tx);
+
+    summary
+}
+
+/// Apply a command in the running engine and wait for its outcome. The engine assigns its
+/// row number. Returns `None` once the engine has stopped.
+pub async fn submit(
+    engine: &mpsc::Sender<EngineRequest>,
+    command: Command,
+    timestamp: Option<u64>,
+) -> Option<Result<(), Rejection>> {
+    let (reply, outcome) = oneshot::channel();
+    engine
+        .send(EngineRequest::Submit(command, timestamp, reply))
+        .await
+        .ok()?;
+    outcome.await.ok()
 }
 
-/// Wait for engine task to finish processing and handle result
-pub async fn finalize_engine(handle: tokio::task::JoinHandle<()>) {
-    if let Err(e) = handle.await {
-        eprintln!("Engine task error: {:?}", e);
-        std::process::exit(1);
+/// Evaluate `f` against the current engine state, in order with the commands sent before it.
+/// Returns `None` once the engine has stopped.
+pub async fn query<T, F>(engine: &mpsc::Sender<EngineRequest>, f: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&State) -> T + Send + 'static,
+{
+    let (reply, result) = oneshot::channel();
+    let query = move |state: &State| {
+        let _ = reply.send(f(state));
+    };
+    engine
+        .send(EngineRequest::Query(Box::new(query)))
+        .await
+        .ok()?;
+    result.await.ok()
+}
+
+/// What became of a single row submitted to a running engine.
+#[derive(Debug, PartialEq)]
+pub enum SubmitOutcome {
+    Applied,
+    /// The engine refused the command.
+    Rejected(Rejection),
+    /// The row is not a valid transaction.
+    Invalid(InputErrorKind),
+    /// The engine has stopped.
+    Stopped,
+}
+
+/// Cloneable handle that validates single rows and applies them in the running engine,
+/// shared by the network front ends.
+#[derive(Clone)]
+pub struct Submitter {
+    engine: mpsc::Sender<EngineRequest>,
+    precision: Precision,
+}
+
+impl Submitter {
+    pub fn new(engine: mpsc::Sender<EngineRequest>, precision: Precision) -> Self {
+        Submitter { engine, precision }
+    }
+
+    pub fn engine(&self) -> &mpsc::Sender<EngineRequest> {
+        &self.engine
+    }
+
+    pub fn precision(&self) -> &Precision {
+        &self.precision
+    }
+
+    /// Validate a row as in strict mode and apply it, waiting for the outcome.
+    pub async fn submit(&self, input: &TransactionInput) -> SubmitOutcome {
+        let command = match input.to_strict_command(&self.precision) {
+            Ok(command) => command,
+            Err(kind) => return SubmitOutcome::Invalid(kind),
+        };
+
+        match submit(&self.engine, command, input.timestamp).await {
+            Some(Ok(())) => SubmitOutcome::Applied,
+            Some(Err(rejection)) => SubmitOutcome::Rejected(rejection),
+            None => SubmitOutcome::Stopped,
+        }
+    }
+}
+
+/// Why the engine could not produce a final state.
+#[derive(Debug)]
+pub enum EngineError {
+    /// The engine task panicked or was cancelled.
+    Task(tokio::task::JoinError),
+    /// A journal or its checkpoints could not be opened.
+    Io(io::Error),
+    /// A journal could not be read back into a state.
+    InvalidJournal(String),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Task(e) => write!(f, "Engine task error: {:?}", e),
+            EngineError::Io(e) => write!(f, "Failed to open journal file: {}", e),
+            EngineError::InvalidJournal(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+/// Wait for engine task to finish processing and return its final state and statistics
+pub async fn finalize_engine(
+    handle: tokio::task::JoinHandle<(State, EngineStats)>,
+) -> Result<(State, EngineStats), EngineError> {
+    handle.await.map_err(EngineError::Task)
+}
+
+/// Rebuild balances as of a point in time from a journal written by an earlier run
+pub fn query_journal(path: &str, as_of: AsOf, client: Option<u16>) -> Result<State, EngineError> {
+    let file = File::open(path).map_err(EngineError::Io)?;
+
+    // Without its checkpoints file, a journal is replayed from the start
+    let checkpoints = match File::open(checkpoints_path(path)) {
+        Ok(file) => Box::new(BufReader::new(file)) as Box<dyn BufRead>,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Box::new(io::empty()),
+        Err(e) => return Err(EngineError::Io(e)),
+    };
+
+    let mut state = state_as_of(BufReader::new(file), checkpoints, as_of)
+        .map_err(EngineError::InvalidJournal)?;
+
+    if let Some(client) = client {
+        state.accounts.retain(|id, _| *id == client);
+    }
+
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_journal_reports_typed_errors() {
+        let res = query_journal("/nonexistent/journal.jsonl", AsOf::Row(1), None);
+        assert!(matches!(res, Err(EngineError::Io(_))));
+
+        let path = std::env::temp_dir().join("payments_engine_runner_journal.jsonl");
+        std::fs::write(&path, "not json\n").unwrap();
+        let res = query_journal(path.to_str().unwrap(), AsOf::Row(1), None);
+        assert!(matches!(res, Err(EngineError::InvalidJournal(_))));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_submitted_commands_are_journaled_in_row_order() {
+        let path = std::env::temp_dir().join("payments_engine_submit_order.jsonl");
+        let path = path.to_str().unwrap().to_string();
+        let checkpoints = crate::engine::journal::checkpoints_path(&path);
+        let journal = JournalWriter::new(
+            BufWriter::new(File::create(&path).unwrap()),
+            BufWriter::new(File::create(&checkpoints).unwrap()),
+            10,
+        );
+        let (cmd_tx, handle) = setup_engine(EngineOptions {
+            journal: Some(journal),
+            ..Default::default()
+        });
+
+        let submitter = Submitter::new(cmd_tx, Precision::default());
+        let clients: Vec<_> = (1..=50)
+            .map(|tx| {
+                let submitter = submitter.clone();
+                tokio::spawn(async move {
+                    let command = Command::Deposit {
+                        client_id: 1,
+                        tx,
+                        amount: rust_decimal::Decimal::ONE,
+                    };
+                    submit(submitter.engine(), command, None).await
+                })
+            })
+            .collect();
+        for client in clients {
+            assert_eq!(client.await.unwrap(), Some(Ok(())));
+        }
+        drop(submitter);
+        finalize_engine(handle).await.unwrap();
+
+        let file = BufReader::new(File::open(&path).unwrap());
+        let rows: Vec<u64> = crate::engine::journal::journal_commands(file)
+            .map(|cmd| cmd.unwrap().row)
+            .collect();
+        assert_eq!(rows, (1..=50).collect::<Vec<_>>());
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&checkpoints).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_finalize_engine_reports_a_failed_task() {
+        let handle = tokio::spawn(async { panic!("engine failed") });
+        let res = finalize_engine(handle).await;
+        assert!(matches!(res, Err(EngineError::Task(_))));
     }
 }
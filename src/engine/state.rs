@@ -1,17 +1,47 @@
 use rust_decimal::Decimal;
-use std::collections::{HashMap, HashSet};
-
-use crate::models::{
-    account::Account,
-    command::Command,
-    transaction::{TransactionRecord, TransactionStatus},
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::{
+    engine::{policy::Policy, rejection::Rejection},
+    models::{
+        account::{Account, LockReason},
+        command::Command,
+        transaction::{TransactionRecord, TransactionStatus},
+    },
 };
 
 /// State of the payments engine, owning all client accounts and transactions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct State {
     pub accounts: HashMap<u16, Account>,
     transactions: HashMap<u32, TransactionRecord>,
     processed_tx_ids: HashSet<u32>,
+    /// Client of each charged-back transaction. A chargeback drops the full record, so only
+    /// this id is kept for status lookups; it grows by one entry per chargeback.
+    #[serde(default)]
+    charged_back: HashMap<u32, u16>,
+    /// Rules applied to commands; not part of a checkpoint.
+    #[serde(skip)]
+    policy: Policy,
+}
+
+/// Accounts and transactions as they stand at a journal checkpoint, covering only what
+/// changed since the previous one. Folding every checkpoint in order rebuilds the state.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StateChanges {
+    accounts: Vec<Account>,
+    transactions: Vec<TransactionChange>,
+}
+
+/// Everything the engine keeps about one transaction id.
+#[derive(Debug, Serialize, Deserialize)]
+struct TransactionChange {
+    tx: u32,
+    processed: bool,
+    /// `None` once a chargeback has dropped the record, or for a rejected command.
+    record: Option<TransactionRecord>,
+    charged_back: Option<u16>,
 }
 
 impl State {
@@ -20,34 +50,181 @@ impl State {
             accounts: HashMap::new(),
             transactions: HashMap::new(),
             processed_tx_ids: HashSet::new(),
+            charged_back: HashMap::new(),
+            policy: Policy::default(),
+        }
+    }
+
+    /// Empty state applying `policy` instead of the current rules.
+    pub fn with_policy(policy: Policy) -> Self {
+        State {
+            policy,
+            ..State::new()
+        }
+    }
+
+    /// Look up a deposit or withdrawal recorded for dispute resolution.
+    pub fn transaction(&self, tx: u32) -> Option<&TransactionRecord> {
+        self.transactions.get(&tx)
+    }
+
+    /// Whether a deposit or withdrawal with this id was ever applied, including withdrawals and
+    /// charged-back deposits that have no record to dispute.
+    pub fn is_processed(&self, tx: u32) -> bool {
+        self.processed_tx_ids.contains(&tx)
+    }
+
+    /// Client of a charged-back transaction, whose record is no longer kept.
+    pub fn charged_back(&self, tx: u32) -> Option<u16> {
+        self.charged_back.get(&tx).copied()
+    }
+
+    /// The current accounts of `clients` and what is known about `txs`.
+    pub fn changes(&self, clients: &BTreeSet<u16>, txs: &BTreeSet<u32>) -> StateChanges {
+        StateChanges {
+            accounts: clients
+                .iter()
+                .filter_map(|client| self.accounts.get(client).cloned())
+                .collect(),
+            transactions: txs
+                .iter()
+                .map(|&tx| TransactionChange {
+                    tx,
+                    processed: self.processed_tx_ids.contains(&tx),
+                    record: self.transactions.get(&tx).cloned(),
+                    charged_back: self.charged_back(tx),
+                })
+                .collect(),
+        }
+    }
+
+    /// The whole state, as changes from an empty one.
+    pub fn all_changes(&self) -> StateChanges {
+        let clients = self.accounts.keys().copied().collect();
+        let txs = self
+            .transactions
+            .keys()
+            .chain(&self.processed_tx_ids)
+            .chain(self.charged_back.keys())
+            .copied()
+            .collect();
+        self.changes(&clients, &txs)
+    }
+
+    /// Bring the accounts and transactions named in `changes` up to date.
+    pub fn apply_changes(&mut self, changes: StateChanges) {
+        for account in changes.accounts {
+            self.accounts.insert(account.client_id, account);
+        }
+
+        for change in changes.transactions {
+            let tx = change.tx;
+            if change.processed {
+                self.processed_tx_ids.insert(tx);
+            }
+            match change.record {
+                Some(record) => self.transactions.insert(tx, record),
+                None => self.transactions.remove(&tx),
+            };
+            if let Some(client) = change.charged_back {
+                self.charged_back.insert(tx, client);
+            }
+        }
+    }
+
+    /// Number of deposits currently under dispute.
+    pub fn open_disputes(&self) -> usize {
+        self.transactions
+            .values()
+            .filter(|record| record.status == TransactionStatus::Disputed)
+            .count()
+    }
+
+    /// Approximate heap memory reserved for transaction records and processed ids.
+    pub fn transaction_store_bytes(&self) -> usize {
+        let record = std::mem::size_of::<(u32, TransactionRecord)>();
+        let id = std::mem::size_of::<u32>();
+        let charged_back = std::mem::size_of::<(u32, u16)>();
+        self.transactions.capacity() * record
+            + self.processed_tx_ids.capacity() * id
+            + self.charged_back.capacity() * charged_back
+    }
+
+    /// Check the bookkeeping holds: no account holds a negative amount, each account holds
+    /// exactly the sum of its disputed deposits, and every locked account records why.
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let mut disputed: HashMap<u16, Decimal> = HashMap::new();
+        for record in self.transactions.values() {
+            if record.status == TransactionStatus::Disputed {
+                *disputed.entry(record.client_id).or_default() += record.amount;
+            }
         }
+
+        for account in self.accounts.values() {
+            let client = account.client_id;
+            let expected = disputed.get(&client).copied().unwrap_or_default();
+            if account.held < Decimal::ZERO {
+                return Err(format!("client {} holds a negative amount", client));
+            }
+            if account.available.checked_add(account.held).is_none() {
+                return Err(format!("client {} total is out of range", client));
+            }
+            if account.held != expected {
+                return Err(format!(
+                    "client {} holds {} but has {} under dispute",
+                    client, account.held, expected
+                ));
+            }
+            if account.locked != account.lock_reason.is_some() {
+                return Err(format!(
+                    "client {} lock state has no matching reason",
+                    client
+                ));
+            }
+        }
+
+        Ok(())
     }
 
-    /// Process a single Command and update state.
+    /// Process a single Command and update state, ignoring commands that are rejected.
     pub fn process_single_command(&mut self, cmd: Command) {
+        let _ = self.apply_command(cmd);
+    }
+
+    /// Apply a single Command, or report why it was rejected without changing any state.
+    /// Balance updates use checked arithmetic, so extreme amounts reject instead of panicking.
+    pub fn apply_command(&mut self, cmd: Command) -> Result<(), Rejection> {
         match cmd {
             Command::Deposit {
                 client_id: client,
                 tx,
                 amount,
             } => {
+                if amount <= Decimal::ZERO {
+                    return Err(Rejection::NonPositiveAmount);
+                }
+
                 if self.processed_tx_ids.contains(&tx) {
-                    return; // duplicate tx id, ignore
+                    return Err(Rejection::DuplicateTx); // duplicate tx id, ignore
                 }
 
                 if self.accounts.get(&client).is_some_and(|acc| acc.locked) {
-                    return;
+                    return Err(Rejection::AccountLocked);
                 }
                 // Create account if not exist
-                let account = self.accounts.entry(client).or_insert_with(|| Account {
-                    client_id: client,
-                    available: Decimal::ZERO,
-                    held: Decimal::ZERO,
-                    locked: false,
-                });
-
-                // Apply deposit
-                account.available += amount;
+                let account = self
+                    .accounts
+                    .entry(client)
+                    .or_insert_with(|| Account::new(client));
+
+                // Apply deposit, keeping the total within range too
+                let held = account.held;
+                account.available = account
+                    .available
+                    .checked_add(amount)
+                    .filter(|available| available.checked_add(held).is_some())
+                    .ok_or(Rejection::Overflow)?;
+                account.deposit_count += 1;
 
                 self.transactions.insert(
                     tx,
@@ -66,117 +243,147 @@ impl State {
                 tx,
                 amount,
             } => {
+                if amount <= Decimal::ZERO {
+                    return Err(Rejection::NonPositiveAmount);
+                }
+
                 // Check for duplicate tx id FIRST
                 if self.processed_tx_ids.contains(&tx) {
-                    return; // duplicate tx id, ignore
+                    return Err(Rejection::DuplicateTx); // duplicate tx id, ignore
                 }
 
                 if self.accounts.get(&client).is_some_and(|acc| acc.locked) {
-                    return;
+                    return Err(Rejection::AccountLocked);
                 }
 
-                let account = self.accounts.entry(client).or_insert_with(|| Account {
-                    client_id: client,
-                    available: Decimal::ZERO,
-                    held: Decimal::ZERO,
-                    locked: false,
-                });
+                let account = self
+                    .accounts
+                    .entry(client)
+                    .or_insert_with(|| Account::new(client));
 
                 // Only withdraw if sufficient available funds
-                if account.available >= amount {
-                    account.available -= amount;
-                    self.processed_tx_ids.insert(tx);
+                if account.available < amount {
+                    // If insufficient funds, withdrawal is ignored (no change, no record)
+                    return Err(Rejection::InsufficientFunds);
                 }
-                // If insufficient funds, withdrawal is ignored (no change, no record)
+
+                account.available -= amount;
+                account.withdrawal_count += 1;
+                self.processed_tx_ids.insert(tx);
             }
             Command::Dispute {
                 client_id: client,
                 tx,
             } => {
                 // Skip if the account is already locked
-                if let Some(account) = self.accounts.get_mut(&client) {
-                    if account.locked {
-                        return; // account is frozen – ignore this dispute
-                    }
+                if self.accounts.get(&client).is_some_and(|acc| acc.locked) {
+                    return Err(Rejection::AccountLocked); // account is frozen – ignore this dispute
                 }
 
                 // Only process if the referenced transaction exists and is a deposit not already disputed
-                if let Some(record) = self.transactions.get_mut(&tx) {
-                    if record.client_id != client {
-                        return; // client ID mismatch, ignore
-                    }
-                    if !record.is_deposit || record.status != TransactionStatus::Normal {
-                        return; // can only dispute normal deposits
+                let record = self.transactions.get_mut(&tx).ok_or(Rejection::UnknownTx)?;
+                if record.client_id != client {
+                    return Err(Rejection::ClientMismatch); // client ID mismatch, ignore
+                }
+                if !record.is_deposit || record.status != TransactionStatus::Normal {
+                    return Err(Rejection::NotDisputable); // can only dispute normal deposits
+                }
+
+                // Adjust account balances: move funds from available to held
+                if let Some(account) = self.accounts.get_mut(&client) {
+                    if !self.policy.allow_negative_available && account.available < record.amount {
+                        return Err(Rejection::InsufficientFunds);
                     }
-                    // Mark transaction as disputed
-                    record.status = TransactionStatus::Disputed;
-                    // Adjust account balances: move funds from available to held
-                    if let Some(account) = self.accounts.get_mut(&client) {
-                        account.available -= record.amount;
-                        account.held += record.amount;
+
+                    let available = account.available.checked_sub(record.amount);
+                    let held = account.held.checked_add(record.amount);
+                    let (Some(available), Some(held)) = (available, held) else {
+                        return Err(Rejection::Overflow);
+                    };
+                    if available.checked_add(held).is_none() {
+                        return Err(Rejection::Overflow);
                     }
+
+                    account.available = available;
+                    account.held = held;
+                    account.dispute_count += 1;
                 }
+                // Mark transaction as disputed
+                record.status = TransactionStatus::Disputed;
             }
             Command::Resolve {
                 client_id: client,
                 tx,
             } => {
                 // Skip if the account is already locked
-                if let Some(account) = self.accounts.get_mut(&client) {
-                    if account.locked {
-                        return; // ignore resolve on a frozen account
-                    }
+                if self.accounts.get(&client).is_some_and(|acc| acc.locked) {
+                    return Err(Rejection::AccountLocked); // ignore resolve on a frozen account
                 }
 
-                if let Some(record) = self.transactions.get_mut(&tx) {
-                    if record.client_id != client {
-                        return;
-                    }
-                    if record.status != TransactionStatus::Disputed {
-                        return; // only resolve an active dispute
-                    }
-                    // Mark transaction back to normal (dispute resolved)
-                    record.status = TransactionStatus::Normal;
-                    // Release held funds back to available
-                    if let Some(account) = self.accounts.get_mut(&client) {
-                        account.held -= record.amount;
-                        account.available += record.amount;
-                    }
+                let record = self.transactions.get_mut(&tx).ok_or(Rejection::UnknownTx)?;
+                if record.client_id != client {
+                    return Err(Rejection::ClientMismatch);
+                }
+                if record.status != TransactionStatus::Disputed {
+                    return Err(Rejection::NotDisputed); // only resolve an active dispute
                 }
+
+                // Release held funds back to available
+                if let Some(account) = self.accounts.get_mut(&client) {
+                    let held = account.held.checked_sub(record.amount);
+                    let available = account.available.checked_add(record.amount);
+                    let (Some(held), Some(available)) = (held, available) else {
+                        return Err(Rejection::Overflow);
+                    };
+
+                    account.held = held;
+                    account.available = available;
+                }
+                // Mark transaction back to normal (dispute resolved)
+                record.status = TransactionStatus::Normal;
             }
             Command::Chargeback {
                 client_id: client,
                 tx,
             } => {
                 // Check the transaction first
-                if let Some(record) = self.transactions.get_mut(&tx) {
-                    if record.client_id != client || record.status != TransactionStatus::Disputed {
-                        return; // only chargeback a valid disputed transaction
-                    }
+                let record = self.transactions.get_mut(&tx).ok_or(Rejection::UnknownTx)?;
+                if record.client_id != client {
+                    return Err(Rejection::ClientMismatch);
+                }
+                if record.status != TransactionStatus::Disputed {
+                    return Err(Rejection::NotDisputed); // only chargeback a valid disputed transaction
+                }
 
-                    // Fetch the account
-                    if let Some(account) = self.accounts.get_mut(&client) {
-                        if account.locked {
-                            return; // ignore chargeback on a frozen account
-                        }
+                // Fetch the account
+                if let Some(account) = self.accounts.get_mut(&client) {
+                    if account.locked {
+                        return Err(Rejection::AccountLocked); // ignore chargeback on a frozen account
+                    }
 
-                        // Finalize chargeback
-                        record.status = TransactionStatus::ChargedBack;
+                    let held = account
+                        .held
+                        .checked_sub(record.amount)
+                        .ok_or(Rejection::Overflow)?;
 
-                        account.held -= record.amount;
+                    // Finalize chargeback
+                    record.status = TransactionStatus::ChargedBack;
 
-                        // Ensure held does not go negative, if your design requires
-                        if account.held < Decimal::ZERO {
-                            account.held = Decimal::ZERO;
-                        }
+                    // Ensure held does not go negative, if your design requires
+                    account.held = held.max(Decimal::ZERO);
 
-                        account.locked = true; // always lock after chargeback
+                    if self.policy.lock_on_chargeback {
+                        account.locked = true;
+                        account.lock_reason = Some(LockReason::Chargeback { tx });
                     }
-
-                    self.transactions.remove(&tx);
                 }
+
+                self.transactions.remove(&tx);
+                self.charged_back.insert(tx, client);
             }
         }
+
+        Ok(())
     }
 }
 
@@ -245,6 +452,27 @@ mod tests {
         assert!(!acc2.locked);
     }
 
+    #[test]
+    fn test_check_invariants_detects_held_mismatch() {
+        let mut state = State::new();
+        state.process_single_command(Command::Deposit {
+            client_id: 2,
+            tx: 10,
+            amount: Decimal::from_str("5.0").unwrap(),
+        });
+        state.process_single_command(Command::Dispute {
+            client_id: 2,
+            tx: 10,
+        });
+        assert_eq!(state.check_invariants(), Ok(()));
+
+        state.accounts.get_mut(&2).unwrap().held = Decimal::ONE;
+        assert_eq!(
+            state.check_invariants(),
+            Err("client 2 holds 1 but has 5.0 under dispute".to_string())
+        );
+    }
+
     #[test]
     fn test_chargeback_locks_account() {
         let mut state = State::new();
@@ -268,6 +496,10 @@ mod tests {
         assert_eq!(acc.available, Decimal::ZERO);
         assert_eq!(acc.held, Decimal::ZERO);
         assert!(acc.locked);
+        assert_eq!(acc.lock_reason, Some(LockReason::Chargeback { tx: 20 }));
+        assert_eq!(state.check_invariants(), Ok(()));
+        assert_eq!(acc.deposit_count, 1);
+        assert_eq!(acc.dispute_count, 1);
         // Further deposits or withdrawals on locked account should be ignored
         state.process_single_command(Command::Deposit {
             client_id: 3,
@@ -302,7 +534,7 @@ mod tests {
         assert_eq!(acc.available, Decimal::from_str("5.0").unwrap());
         assert_eq!(acc.held, Decimal::ZERO);
         assert!(!acc.locked);
-        assert!(state.transactions.get(&101).is_none());
+        assert!(!state.transactions.contains_key(&101));
     }
 
     #[test]
@@ -353,8 +585,8 @@ mod tests {
             tx: 400,
         });
         // No account or transaction should be created
-        assert!(state.accounts.get(&8).is_none());
-        assert!(state.transactions.get(&400).is_none());
+        assert!(!state.accounts.contains_key(&8));
+        assert!(!state.transactions.contains_key(&400));
     }
 
     #[test]
@@ -455,7 +687,7 @@ mod tests {
             tx: 2000,
             amount: Decimal::from_str("5.0").unwrap(),
         });
-        assert!(state.transactions.get(&2000).is_none());
+        assert!(!state.transactions.contains_key(&2000));
     }
 
     #[test]
@@ -533,4 +765,168 @@ mod tests {
         assert_eq!(acc.held, Decimal::ZERO);
         assert!(acc.locked);
     }
+
+    #[test]
+    fn test_non_positive_amounts_are_rejected() {
+        let mut state = State::new();
+        let rejected = state.apply_command(Command::Deposit {
+            client_id: 50,
+            tx: 5000,
+            amount: Decimal::ZERO,
+        });
+        assert_eq!(rejected, Err(Rejection::NonPositiveAmount));
+
+        let rejected = state.apply_command(Command::Withdrawal {
+            client_id: 50,
+            tx: 5001,
+            amount: Decimal::from_str("-1.0").unwrap(),
+        });
+        assert_eq!(rejected, Err(Rejection::NonPositiveAmount));
+
+        // Neither an account nor a transaction id is consumed
+        assert!(!state.accounts.contains_key(&50));
+        assert!(!state.processed_tx_ids.contains(&5000));
+    }
+
+    #[test]
+    fn test_deposit_overflow_is_rejected() {
+        let mut state = State::new();
+        assert_eq!(
+            state.apply_command(Command::Deposit {
+                client_id: 51,
+                tx: 5100,
+                amount: Decimal::MAX,
+            }),
+            Ok(())
+        );
+        assert_eq!(
+            state.apply_command(Command::Deposit {
+                client_id: 51,
+                tx: 5101,
+                amount: Decimal::ONE,
+            }),
+            Err(Rejection::Overflow)
+        );
+
+        let acc = state.accounts.get(&51).unwrap();
+        assert_eq!(acc.available, Decimal::MAX);
+        assert_eq!(acc.deposit_count, 1);
+        assert!(!state.transactions.contains_key(&5101));
+    }
+
+    #[test]
+    fn test_dispute_overflow_is_rejected() {
+        let mut state = State::new();
+        // Drive available to -MAX with MAX held, then dispute another MAX deposit
+        for cmd in [
+            Command::Deposit {
+                client_id: 52,
+                tx: 5200,
+                amount: Decimal::MAX,
+            },
+            Command::Withdrawal {
+                client_id: 52,
+                tx: 5201,
+                amount: Decimal::MAX,
+            },
+            Command::Dispute {
+                client_id: 52,
+                tx: 5200,
+            },
+            Command::Deposit {
+                client_id: 52,
+                tx: 5202,
+                amount: Decimal::MAX,
+            },
+        ] {
+            assert_eq!(state.apply_command(cmd), Ok(()));
+        }
+
+        let rejected = state.apply_command(Command::Dispute {
+            client_id: 52,
+            tx: 5202,
+        });
+        assert_eq!(rejected, Err(Rejection::Overflow));
+
+        let acc = state.accounts.get(&52).unwrap();
+        assert_eq!(acc.available, Decimal::ZERO);
+        assert_eq!(acc.held, Decimal::MAX);
+        let tx_record = state.transactions.get(&5202).unwrap();
+        assert_eq!(tx_record.status, TransactionStatus::Normal);
+    }
+
+    #[test]
+    fn test_deposit_overflowing_total_is_rejected() {
+        let mut state = State::new();
+        for cmd in [
+            Command::Deposit {
+                client_id: 54,
+                tx: 5400,
+                amount: Decimal::MAX,
+            },
+            Command::Dispute {
+                client_id: 54,
+                tx: 5400,
+            },
+        ] {
+            assert_eq!(state.apply_command(cmd), Ok(()));
+        }
+
+        // available alone has room, but available + held would overflow
+        let rejected = state.apply_command(Command::Deposit {
+            client_id: 54,
+            tx: 5401,
+            amount: Decimal::MAX,
+        });
+        assert_eq!(rejected, Err(Rejection::Overflow));
+
+        let acc = state.accounts.get(&54).unwrap();
+        assert_eq!(acc.available, Decimal::ZERO);
+        assert_eq!(acc.total(), Decimal::MAX);
+        assert!(!state.transactions.contains_key(&5401));
+        assert_eq!(state.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_rejection_reasons() {
+        let mut state = State::new();
+        state.process_single_command(Command::Deposit {
+            client_id: 53,
+            tx: 5300,
+            amount: Decimal::from_str("1.0").unwrap(),
+        });
+
+        let dispute = |client_id, tx| Command::Dispute { client_id, tx };
+        assert_eq!(
+            state.apply_command(dispute(53, 9999)),
+            Err(Rejection::UnknownTx)
+        );
+        assert_eq!(
+            state.apply_command(dispute(54, 5300)),
+            Err(Rejection::ClientMismatch)
+        );
+        assert_eq!(
+            state.apply_command(Command::Resolve {
+                client_id: 53,
+                tx: 5300
+            }),
+            Err(Rejection::NotDisputed)
+        );
+        assert_eq!(
+            state.apply_command(Command::Withdrawal {
+                client_id: 53,
+                tx: 5301,
+                amount: Decimal::from_str("2.0").unwrap(),
+            }),
+            Err(Rejection::InsufficientFunds)
+        );
+        assert_eq!(
+            state.apply_command(Command::Deposit {
+                client_id: 53,
+                tx: 5300,
+                amount: Decimal::from_str("2.0").unwrap(),
+            }),
+            Err(Rejection::DuplicateTx)
+        );
+    }
 }
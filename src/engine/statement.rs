@@ -0,0 +1,141 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::io::{self, Write};
+
+use crate::{
+    adapters::input::InputRecord,
+    engine::state::State,
+    models::{error::InputError, precision::Precision},
+};
+
+/// One command of a client with its outcome and the account balances right after it.
+#[derive(Debug, Serialize)]
+pub struct StatementLine {
+    pub row: u64,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub tx: u32,
+    #[serde(with = "rust_decimal::serde::str_option")]
+    pub amount: Option<Decimal>,
+    /// `applied`, or the reason the engine rejected the command.
+    pub outcome: &'static str,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub available: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub held: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub total: Decimal,
+    pub locked: bool,
+}
+
+/// Apply every record, since other clients' rows can affect this one (a reused tx id is a
+/// duplicate whoever sent it), and list the commands of `client` in input order.
+/// Unreadable rows are skipped, or end the statement with an error in strict mode.
+pub fn statement<I>(
+    records: I,
+    client: u16,
+    precision: &Precision,
+    strict: bool,
+) -> Result<Vec<StatementLine>, InputError>
+where
+    I: Iterator<Item = InputRecord>,
+{
+    let mut state = State::new();
+    let mut lines = Vec::new();
+
+    for result in records {
+        let converted = result.and_then(|(position, input)| {
+            let convert = match strict {
+                true => input.to_strict_command(precision),
+                false => input.to_command_with_precision(precision),
+            };
+            convert
+                .map(|cmd| (position, cmd))
+                .map_err(|kind| InputError::new(kind, Some(position)))
+        });
+        let (position, cmd) = match converted {
+            Ok(converted) => converted,
+            Err(e) if strict => return Err(e),
+            Err(_) => continue,
+        };
+
+        let (kind, tx, amount) = (cmd.kind(), cmd.tx(), cmd.amount());
+        let is_client = cmd.client_id() == client;
+        let outcome = state.apply_command(cmd);
+
+        if is_client {
+            let account = state.accounts.get(&client);
+            let available = account.map_or(Decimal::ZERO, |acc| acc.available);
+            let held = account.map_or(Decimal::ZERO, |acc| acc.held);
+            let total = account.map_or(Decimal::ZERO, |acc| acc.total());
+
+            lines.push(StatementLine {
+                row: position.line,
+                kind,
+                tx,
+                amount: amount.map(|amount| precision.normalize(amount)),
+                outcome: outcome.map_or_else(|r| r.as_str(), |()| "applied"),
+                available: precision.normalize(available),
+                held: precision.normalize(held),
+                total: precision.normalize(total),
+                locked: account.is_some_and(|acc| acc.locked),
+            });
+        }
+    }
+
+    Ok(lines)
+}
+
+/// Write statement lines as CSV with a header row.
+pub fn write_statement<W: Write>(lines: &[StatementLine], writer: W) -> io::Result<()> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    for line in lines {
+        wtr.serialize(line)?;
+    }
+    wtr.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::csv_parser::{build_csv_reader, read_transactions};
+
+    #[test]
+    fn test_statement_lists_client_rows_with_running_balances() {
+        let data = "type,client,tx,amount\n\
+                    deposit,1,1,10.0\n\
+                    deposit,2,2,5.0\n\
+                    withdrawal,1,3,20.0\n\
+                    deposit,1,2,1.0\n\
+                    dispute,1,1,\n\
+                    bogus,1,4,1.0\n";
+        let mut reader = build_csv_reader(data.as_bytes(), false);
+        let lines = statement(
+            read_transactions(&mut reader),
+            1,
+            &Precision::default(),
+            false,
+        )
+        .unwrap();
+
+        let mut out = Vec::new();
+        write_statement(&lines, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "row,type,tx,amount,outcome,available,held,total,locked\n\
+             2,deposit,1,10.0000,applied,10.0000,0.0000,10.0000,false\n\
+             4,withdrawal,3,20.0000,insufficient_funds,10.0000,0.0000,10.0000,false\n\
+             5,deposit,2,1.0000,duplicate_tx,10.0000,0.0000,10.0000,false\n\
+             6,dispute,1,,applied,0.0000,10.0000,10.0000,false\n"
+        );
+
+        let mut reader = build_csv_reader(data.as_bytes(), false);
+        let strict = statement(
+            read_transactions(&mut reader),
+            1,
+            &Precision::default(),
+            true,
+        );
+        assert_eq!(strict.unwrap_err().position.map(|p| p.line), Some(7));
+    }
+}
@@ -0,0 +1,204 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    time::Duration,
+};
+
+use crate::{
+    adapters::output::write_atomic,
+    engine::{rejection::Rejection, runner::IngestSummary, state::State},
+    models::command::Command,
+};
+
+/// Counts and totals the engine gathers while applying commands.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct EngineStats {
+    pub commands_by_type: BTreeMap<&'static str, usize>,
+    pub applied: usize,
+    pub rejected_by_reason: BTreeMap<&'static str, usize>,
+    pub deposited: Decimal,
+    pub withdrawn: Decimal,
+    pub charged_back: Decimal,
+}
+
+impl EngineStats {
+    /// Apply a command to `state` and account for its outcome.
+    pub fn apply(&mut self, state: &mut State, cmd: Command) -> Result<(), Rejection> {
+        // A chargeback drops its transaction record, so look the amount up first.
+        let referenced = state.transaction(cmd.tx()).map(|record| record.amount);
+
+        let outcome = state.apply_command(cmd.clone());
+        *self.commands_by_type.entry(cmd.kind()).or_default() += 1;
+
+        if let Err(rejection) = outcome {
+            *self
+                .rejected_by_reason
+                .entry(rejection.as_str())
+                .or_default() += 1;
+            return outcome;
+        }
+        self.applied += 1;
+
+        // Totals only ever grow, saturating keeps a report for absurd inputs.
+        match cmd {
+            Command::Deposit { amount, .. } => {
+                self.deposited = self.deposited.saturating_add(amount);
+            }
+            Command::Withdrawal { amount, .. } => {
+                self.withdrawn = self.withdrawn.saturating_add(amount);
+            }
+            Command::Chargeback { .. } => {
+                let amount = referenced.unwrap_or_default();
+                self.charged_back = self.charged_back.saturating_add(amount);
+            }
+            Command::Dispute { .. } | Command::Resolve { .. } => {}
+        }
+
+        outcome
+    }
+}
+
+/// Wall-clock time spent in each phase of a run, in milliseconds.
+#[derive(Debug, Default, Serialize)]
+pub struct PhaseTimings {
+    /// Reading and converting input, while the engine applies commands concurrently.
+    pub ingest_ms: f64,
+    /// Waiting for the engine to drain the remaining commands.
+    pub engine_ms: f64,
+    /// Writing account balances.
+    pub output_ms: f64,
+}
+
+impl PhaseTimings {
+    pub fn millis(duration: Duration) -> f64 {
+        duration.as_secs_f64() * 1000.0
+    }
+}
+
+/// Machine-readable summary of a whole run.
+#[derive(Debug, Serialize)]
+pub struct RunSummary<'a> {
+    pub rows_processed: usize,
+    pub rows_skipped: usize,
+    pub skipped_by_reason: &'a BTreeMap<&'static str, usize>,
+    #[serde(flatten)]
+    pub engine: &'a EngineStats,
+    pub held: Decimal,
+    pub accounts_created: usize,
+    pub accounts_locked: usize,
+    pub timings: PhaseTimings,
+}
+
+impl<'a> RunSummary<'a> {
+    pub fn new(
+        ingest: &'a IngestSummary,
+        engine: &'a EngineStats,
+        state: &State,
+        timings: PhaseTimings,
+    ) -> Self {
+        let accounts = state.accounts.values();
+
+        RunSummary {
+            rows_processed: ingest.processed,
+            rows_skipped: ingest.skipped,
+            skipped_by_reason: &ingest.skipped_by_reason,
+            engine,
+            held: accounts
+                .clone()
+                .fold(Decimal::ZERO, |sum, acc| sum.saturating_add(acc.held)),
+            accounts_created: state.accounts.len(),
+            accounts_locked: accounts.filter(|acc| acc.locked).count(),
+            timings,
+        }
+    }
+}
+
+/// Write the summary as pretty-printed JSON.
+pub fn write_summary(path: &str, summary: &RunSummary) -> io::Result<()> {
+    write_atomic(path, |writer| {
+        serde_json::to_writer_pretty(&mut *writer, summary)?;
+        writer.write_all(b"\n")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn run(commands: Vec<Command>) -> (State, EngineStats) {
+        let mut state = State::new();
+        let mut stats = EngineStats::default();
+        for cmd in commands {
+            let _ = stats.apply(&mut state, cmd);
+        }
+        (state, stats)
+    }
+
+    #[test]
+    fn test_stats_and_summary() {
+        let (state, stats) = run(vec![
+            Command::Deposit {
+                client_id: 1,
+                tx: 1,
+                amount: dec("10.0"),
+            },
+            Command::Deposit {
+                client_id: 2,
+                tx: 2,
+                amount: dec("4.0"),
+            },
+            Command::Withdrawal {
+                client_id: 1,
+                tx: 3,
+                amount: dec("3.0"),
+            },
+            Command::Withdrawal {
+                client_id: 1,
+                tx: 4,
+                amount: dec("30.0"),
+            },
+            Command::Dispute {
+                client_id: 2,
+                tx: 2,
+            },
+            Command::Chargeback {
+                client_id: 2,
+                tx: 2,
+            },
+            Command::Dispute {
+                client_id: 1,
+                tx: 1,
+            },
+            Command::Dispute {
+                client_id: 1,
+                tx: 99,
+            },
+        ]);
+
+        assert_eq!(stats.commands_by_type.get("dispute"), Some(&3));
+        assert_eq!(stats.applied, 6);
+        assert_eq!(stats.rejected_by_reason.get("insufficient_funds"), Some(&1));
+        assert_eq!(stats.rejected_by_reason.get("unknown_tx"), Some(&1));
+        assert_eq!(stats.deposited, dec("14.0"));
+        assert_eq!(stats.withdrawn, dec("3.0"));
+        assert_eq!(stats.charged_back, dec("4.0"));
+
+        let ingest = IngestSummary::default();
+        let summary = RunSummary::new(&ingest, &stats, &state, PhaseTimings::default());
+        assert_eq!(summary.held, dec("10.0"));
+        assert_eq!(summary.accounts_created, 2);
+        assert_eq!(summary.accounts_locked, 1);
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["applied"], 6);
+        assert_eq!(json["charged_back"], "4.0");
+        assert!(json["timings"]["ingest_ms"].is_number());
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::{
+    adapters::input::InputRecord,
+    engine::{rejection::Rejection, state::State},
+    models::{error::InputError, precision::Precision},
+};
+
+/// Outcome of a dry run over an input, without any balances.
+#[derive(Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    /// Rows that converted to a command, by transaction type.
+    pub rows_by_type: BTreeMap<&'static str, usize>,
+    /// Rows that could not be turned into a command at all, by `InputErrorKind` reason.
+    pub invalid_by_reason: BTreeMap<&'static str, usize>,
+    /// Commands the engine refused, by `Rejection` reason.
+    pub rejected_by_reason: BTreeMap<&'static str, usize>,
+    /// Rows that could not be turned into a command at all.
+    pub invalid_rows: usize,
+    /// Disputes, resolves and chargebacks carrying an amount, which is ignored outside strict
+    /// mode; in strict mode they are invalid rows instead.
+    pub ignored_amounts: usize,
+    /// Deposits and withdrawals reusing a transaction id.
+    pub duplicate_tx_ids: BTreeSet<u32>,
+    /// Disputes, resolves and chargebacks naming a transaction that does not exist.
+    pub unknown_tx_refs: BTreeSet<u32>,
+    pub locked_clients: BTreeSet<u16>,
+}
+
+impl ValidationReport {
+    /// Whether every row could be read and converted; engine rejections are business outcomes.
+    pub fn is_valid(&self) -> bool {
+        self.invalid_rows == 0
+    }
+}
+
+/// Run every record through conversion and the engine rules, collecting what would happen.
+/// In strict mode rows are converted as a strict run would, so more of them can be invalid.
+pub fn validate<I>(records: I, precision: &Precision, strict: bool) -> ValidationReport
+where
+    I: Iterator<Item = InputRecord>,
+{
+    let mut report = ValidationReport::default();
+    let mut state = State::new();
+
+    for result in records {
+        let converted = result.map(|(_, input)| match strict {
+            true => input.to_strict_command(precision),
+            false => {
+                report.ignored_amounts += usize::from(input.unexpected_amount().is_some());
+                input.to_command_with_precision(precision)
+            }
+        });
+        let cmd = match converted {
+            Ok(Ok(cmd)) => cmd,
+            Ok(Err(kind)) | Err(InputError { kind, .. }) => {
+                *report.invalid_by_reason.entry(kind.as_str()).or_default() += 1;
+                report.invalid_rows += 1;
+                continue;
+            }
+        };
+
+        *report.rows_by_type.entry(cmd.kind()).or_default() += 1;
+        let tx = cmd.tx();
+
+        if let Err(rejection) = state.apply_command(cmd) {
+            *report
+                .rejected_by_reason
+                .entry(rejection.as_str())
+                .or_default() += 1;
+            match rejection {
+                Rejection::DuplicateTx => {
+                    report.duplicate_tx_ids.insert(tx);
+                }
+                // Known withdrawals and charged-back deposits are rejected the same way
+                Rejection::UnknownTx if !state.is_processed(tx) => {
+                    report.unknown_tx_refs.insert(tx);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    report.locked_clients = state
+        .accounts
+        .values()
+        .filter(|acc| acc.locked)
+        .map(|acc| acc.client_id)
+        .collect();
+
+    report
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn join<T: ToString>(items: &BTreeSet<T>) -> String {
+            match items.is_empty() {
+                true => "none".into(),
+                false => items
+                    .iter()
+                    .map(T::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            }
+        }
+
+        writeln!(f, "Rows by type:")?;
+        for (kind, count) in &self.rows_by_type {
+            writeln!(f, "  {}: {}", kind, count)?;
+        }
+        writeln!(f, "Invalid rows by reason:")?;
+        for (reason, count) in &self.invalid_by_reason {
+            writeln!(f, "  {}: {}", reason, count)?;
+        }
+        writeln!(f, "Rejected rows by reason:")?;
+        for (reason, count) in &self.rejected_by_reason {
+            writeln!(f, "  {}: {}", reason, count)?;
+        }
+        if self.ignored_amounts > 0 {
+            writeln!(
+                f,
+                "Amounts ignored on disputes, resolves and chargebacks: {}",
+                self.ignored_amounts
+            )?;
+        }
+        writeln!(f, "Duplicate tx ids: {}", join(&self.duplicate_tx_ids))?;
+        writeln!(f, "Unknown tx references: {}", join(&self.unknown_tx_refs))?;
+        writeln!(f, "Clients ending locked: {}", join(&self.locked_clients))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::csv_parser::{build_csv_reader, read_transactions};
+
+    fn validate_csv(input: &str) -> ValidationReport {
+        let mut reader = build_csv_reader(input.as_bytes(), false);
+        validate(read_transactions(&mut reader), &Precision::default(), false)
+    }
+
+    #[test]
+    fn test_validate_collects_outcomes() {
+        let report = validate_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,5.0\n\
+             deposit,1,1,5.0\n\
+             withdrawal,1,2,10.0\n\
+             dispute,1,9,\n\
+             refund,1,3,1.0\n\
+             deposit,x,4,1.0\n\
+             dispute,1,1,\n\
+             chargeback,1,1,\n\
+             deposit,2,5,3.0\n\
+             withdrawal,2,6,1.0\n\
+             dispute,2,6,\n\
+             chargeback,1,1,\n",
+        );
+
+        assert_eq!(report.rows_by_type.get("deposit"), Some(&3));
+        assert_eq!(report.rows_by_type.get("dispute"), Some(&3));
+        assert_eq!(report.rows_by_type.get("refund"), None);
+        assert_eq!(report.rejected_by_reason.get("duplicate_tx"), Some(&1));
+        assert_eq!(
+            report.rejected_by_reason.get("insufficient_funds"),
+            Some(&1)
+        );
+        assert_eq!(report.invalid_by_reason.get("unknown_type"), Some(&1));
+        assert_eq!(report.invalid_by_reason.get("bad_id"), Some(&1));
+        assert_eq!(report.rejected_by_reason.get("unknown_tx"), Some(&3));
+        assert_eq!(report.invalid_rows, 2);
+        assert!(!report.is_valid());
+        assert_eq!(report.duplicate_tx_ids, BTreeSet::from([1]));
+        assert_eq!(report.unknown_tx_refs, BTreeSet::from([9]));
+        assert_eq!(report.locked_clients, BTreeSet::from([1]));
+
+        let text = report.to_string();
+        assert!(text.contains("  deposit: 3\n"));
+        assert!(text.contains("Unknown tx references: 9\n"));
+        assert!(text.ends_with("Clients ending locked: 1\n"));
+    }
+
+    #[test]
+    fn test_validate_counts_unexpected_amounts() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,5.0\n\
+                     dispute,1,1,5.0\n\
+                     resolve,1,1,5.0\n";
+
+        let lenient = validate_csv(input);
+        assert!(lenient.is_valid());
+        assert_eq!(lenient.ignored_amounts, 2);
+        assert_eq!(lenient.rows_by_type.get("dispute"), Some(&1));
+        assert!(
+            lenient
+                .to_string()
+                .contains("Amounts ignored on disputes, resolves and chargebacks: 2\n")
+        );
+
+        let mut reader = build_csv_reader(input.as_bytes(), true);
+        let strict = validate(read_transactions(&mut reader), &Precision::default(), true);
+        assert!(!strict.is_valid());
+        assert_eq!(strict.ignored_amounts, 0);
+        assert_eq!(strict.invalid_rows, 2);
+        assert_eq!(strict.invalid_by_reason.get("unexpected_amount"), Some(&2));
+        assert_eq!(strict.rows_by_type.get("dispute"), None);
+    }
+
+    #[test]
+    fn test_validate_keeps_input_and_engine_reasons_apart() {
+        // Both the parser and the engine call a zero amount `non_positive_amount`
+        let report = validate_csv("type,client,tx,amount\ndeposit,1,1,0\n");
+        assert_eq!(
+            report.invalid_by_reason.get("non_positive_amount"),
+            Some(&1)
+        );
+        assert!(report.rejected_by_reason.is_empty());
+        assert!(report.to_string().contains(
+            "Invalid rows by reason:\n  non_positive_amount: 1\nRejected rows by reason:\n"
+        ));
+    }
+
+    #[test]
+    fn test_validate_clean_input() {
+        let report = validate_csv("type,client,tx,amount\ndeposit,1,1,5.0\n");
+        assert!(report.is_valid());
+        assert!(report.invalid_by_reason.is_empty());
+        assert!(report.rejected_by_reason.is_empty());
+        assert!(report.to_string().contains("Duplicate tx ids: none\n"));
+    }
+}
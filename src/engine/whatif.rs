@@ -0,0 +1,241 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use crate::{
+    adapters::{accounts_file::accounts_from_state, input::InputRecord},
+    engine::{
+        diff::{self, DiffOverflow, DiffReport},
+        policy::Policy,
+        rejection::Rejection,
+        state::State,
+    },
+    models::{command::Command, error::InputError, precision::Precision},
+};
+
+/// A command the current rules and the candidate policy handle differently.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OutcomeChange {
+    pub row: u64,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub client: u16,
+    pub tx: u32,
+    #[serde(with = "rust_decimal::serde::str_option")]
+    pub amount: Option<Decimal>,
+    /// `applied`, or the reason the command was rejected, under each set of rules.
+    pub current: &'static str,
+    pub candidate: &'static str,
+}
+
+/// Commands whose outcome changes under the candidate policy, and how the final balances move
+/// from the current rules to the candidate ones.
+#[derive(Debug, PartialEq)]
+pub struct WhatIfReport {
+    pub commands: Vec<OutcomeChange>,
+    pub accounts: DiffReport,
+}
+
+/// Runs the same commands through the current rules and a candidate policy side by side.
+pub struct WhatIf {
+    current: State,
+    candidate: State,
+    changes: Vec<OutcomeChange>,
+}
+
+impl WhatIf {
+    pub fn new(policy: Policy) -> Self {
+        WhatIf {
+            current: State::new(),
+            candidate: State::with_policy(policy),
+            changes: Vec::new(),
+        }
+    }
+
+    /// Apply the command read from input line `row` under both sets of rules.
+    pub fn apply(&mut self, row: u64, cmd: Command) {
+        let (kind, client, tx, amount) = (cmd.kind(), cmd.client_id(), cmd.tx(), cmd.amount());
+        let current = outcome(self.current.apply_command(cmd.clone()));
+        let candidate = outcome(self.candidate.apply_command(cmd));
+
+        if current != candidate {
+            self.changes.push(OutcomeChange {
+                row,
+                kind,
+                client,
+                tx,
+                amount,
+                current,
+                candidate,
+            });
+        }
+    }
+
+    /// The changed outcomes, and how the final balances move; fails if a balance change
+    /// overflows.
+    pub fn report(self, precision: &Precision) -> Result<WhatIfReport, DiffOverflow> {
+        let mut commands = self.changes;
+        for change in &mut commands {
+            change.amount = change.amount.map(|amount| precision.normalize(amount));
+        }
+
+        let current = accounts_from_state(&self.current.accounts, precision);
+        let candidate = accounts_from_state(&self.candidate.accounts, precision);
+        Ok(WhatIfReport {
+            commands,
+            accounts: diff::diff(&current, &candidate)?,
+        })
+    }
+}
+
+fn outcome(result: Result<(), Rejection>) -> &'static str {
+    result.map_or_else(|r| r.as_str(), |()| "applied")
+}
+
+/// Run input records through both sets of rules. Unreadable rows are skipped, or end the run
+/// with an error in strict mode.
+pub fn what_if<I>(
+    records: I,
+    policy: Policy,
+    precision: &Precision,
+    strict: bool,
+) -> Result<WhatIf, InputError>
+where
+    I: Iterator<Item = InputRecord>,
+{
+    let mut what_if = WhatIf::new(policy);
+
+    for result in records {
+        let converted = result.and_then(|(position, input)| {
+            let convert = match strict {
+                true => input.to_strict_command(precision),
+                false => input.to_command_with_precision(precision),
+            };
+            convert
+                .map(|cmd| (position, cmd))
+                .map_err(|kind| InputError::new(kind, Some(position)))
+        });
+        match converted {
+            Ok((position, cmd)) => what_if.apply(position.line, cmd),
+            Err(e) if strict => return Err(e),
+            Err(_) => continue,
+        }
+    }
+
+    Ok(what_if)
+}
+
+impl WhatIfReport {
+    /// Write one CSV row per command with a different outcome, with a header row.
+    pub fn write_csv<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut wtr = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(writer);
+
+        wtr.write_record([
+            "row",
+            "type",
+            "client",
+            "tx",
+            "amount",
+            "current",
+            "candidate",
+        ])?;
+        for change in &self.commands {
+            wtr.serialize(change)?;
+        }
+        wtr.flush()
+    }
+}
+
+impl fmt::Display for WhatIfReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Commands with a different outcome: {}",
+            self.commands.len()
+        )?;
+        for change in &self.commands {
+            write!(
+                f,
+                "  row {}: {} client {} tx {}",
+                change.row, change.kind, change.client, change.tx
+            )?;
+            if let Some(amount) = change.amount {
+                write!(f, " amount {}", amount)?;
+            }
+            writeln!(f, ": {} -> {}", change.current, change.candidate)?;
+        }
+        write!(f, "{}", self.accounts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::csv_parser::{build_csv_reader, read_transactions};
+
+    #[test]
+    fn test_what_if_reports_changed_outcomes_and_balances() {
+        let data = "type,client,tx,amount\n\
+                    deposit,1,1,10.0\n\
+                    withdrawal,1,2,8.0\n\
+                    dispute,1,1,\n\
+                    chargeback,1,1,\n\
+                    deposit,1,3,5.0\n\
+                    deposit,2,4,1.0\n";
+        let policy = Policy {
+            allow_negative_available: false,
+            ..Policy::default()
+        };
+        let mut reader = build_csv_reader(data.as_bytes(), false);
+        let report = what_if(
+            read_transactions(&mut reader),
+            policy,
+            &Precision::default(),
+            false,
+        )
+        .unwrap()
+        .report(&Precision::default())
+        .unwrap();
+
+        let mut csv = Vec::new();
+        report.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "row,type,client,tx,amount,current,candidate\n\
+             4,dispute,1,1,,applied,insufficient_funds\n\
+             5,chargeback,1,1,,applied,not_disputed\n\
+             6,deposit,1,3,5.0000,account_locked,applied\n"
+        );
+
+        assert_eq!(
+            report.to_string(),
+            "Commands with a different outcome: 3\n  \
+             row 4: dispute client 1 tx 1: applied -> insufficient_funds\n  \
+             row 5: chargeback client 1 tx 1: applied -> not_disputed\n  \
+             row 6: deposit client 1 tx 3 amount 5.0000: account_locked -> applied\n\
+             New clients: 0\n\
+             Removed clients: 0\n\
+             Changed clients: 1\n  \
+             ~ client 1: available +15.0000, held 0.0000, total +15.0000, locked true -> false\n\
+             Net change: available +15.0000, held 0.0000, total +15.0000\n"
+        );
+
+        let mut reader = build_csv_reader(data.as_bytes(), false);
+        let same = what_if(
+            read_transactions(&mut reader),
+            Policy::default(),
+            &Precision::default(),
+            false,
+        )
+        .unwrap()
+        .report(&Precision::default())
+        .unwrap();
+        assert!(same.commands.is_empty());
+        assert!(same.accounts.accounts.is_empty());
+    }
+}
@@ -0,0 +1,7 @@
+//! Payments engine: the transaction models, the engine applying them to client accounts, and
+//! the adapters reading input and writing balances. The `payments_engine` binary is the
+//! command-line front end over this library.
+
+pub mod adapters;
+pub mod engine;
+pub mod models;
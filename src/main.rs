@@ -1,19 +1,583 @@
-mod adapters;
-mod engine;
+use payments_engine::{adapters, engine, models};
 
-mod models;
+use adapters::{
+    accounts_file::{accounts_from_state, read_accounts_file, read_snapshot},
+    cli::{CliArgs, ExitCode, Mode},
+    follow::FollowReader,
+    input::{InputFormat, open_input},
+    jsonl_parser::JsonlReader,
+    output::{OutputOptions, write_accounts, write_accounts_to_file, write_atomic},
+    stream::StreamAddr,
+};
+use engine::{
+    checkpoint::read_checkpoint,
+    diff::{self, DiffFormat},
+    journal::{
+        JournalWriter, checkpoints_length_through, checkpoints_path, journal_commands,
+        journal_length_through,
+    },
+    metrics::Metrics,
+    policy::load_policy,
+    reconcile,
+    runner::{
+        self, EngineError, EngineOptions, EngineRequest, IngestOptions, IngestSummary,
+        SnapshotOptions,
+    },
+    state::State,
+    statement,
+    stats::{PhaseTimings, RunSummary, write_summary},
+    validation,
+    whatif::{self, WhatIf, WhatIfReport},
+};
+use models::error::Position;
+use tokio::sync::mpsc::Sender;
+use tracing::{error, info};
 
-use engine::runner;
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 #[tokio::main]
 async fn main() {
-    let file_path = adapters::cli::parse_file_path_from_cli_args();
+    let cli = adapters::cli::parse_cli_args();
 
-    let mut csv_reader = adapters::csv_parser::build_csv_reader(&file_path);
+    if let Err(e) = adapters::logging::init(cli.log_level.as_deref(), cli.log_format) {
+        eprintln!("{}", e);
+        ExitCode::Usage.exit();
+    }
 
-    let (cmd_tx, engine_handle) = runner::setup_engine();
+    let output = OutputOptions {
+        format: cli.output_format,
+        sort: cli.sort,
+        precision: cli.precision,
+    };
 
-    runner::send_commands_to_engine(&mut csv_reader, cmd_tx).await;
+    if cli.mode == Mode::Diff {
+        let read = |path: &str| {
+            read_snapshot(path, &cli.precision).unwrap_or_else(|e| {
+                error!("{}", e);
+                ExitCode::Io.exit();
+            })
+        };
+        let before = read(cli.input.as_deref().expect("diff requires two snapshots"));
+        let after = read(cli.after.as_deref().expect("diff requires two snapshots"));
+        let report = diff::diff(&before, &after).unwrap_or_else(|e| {
+            error!("{}", e);
+            ExitCode::InvalidInput.exit();
+        });
 
-    runner::finalize_engine(engine_handle).await;
+        let write = |writer: &mut dyn Write| match cli.diff_format {
+            DiffFormat::Human => write!(writer, "{}", report),
+            DiffFormat::Csv => report.write_csv(writer),
+        };
+        let written = match &cli.output {
+            Some(path) => write_atomic(path, |writer| write(writer)),
+            None => write(&mut io::stdout()),
+        };
+        if let Err(e) = written {
+            error!("Failed to write diff: {}", e);
+            ExitCode::Io.exit();
+        }
+        return;
+    }
+
+    if cli.mode == Mode::Replay {
+        // validated by the CLI parser: a replay always has a journal and a point in time
+        let journal_path = cli.input.as_deref().expect("replay requires a journal");
+        let as_of = cli.as_of.expect("replay requires a point in time");
+        let state = runner::query_journal(journal_path, as_of, cli.client)
+            .unwrap_or_else(|e| engine_failed(e));
+        write_final_accounts(&cli, &state, &output);
+        return;
+    }
+
+    if matches!(cli.mode, Mode::Serve | Mode::Stream) {
+        let engine = EngineOptions {
+            journal: open_journal(&cli, None),
+            metrics: start_metrics(&cli).await,
+            ..Default::default()
+        };
+        let (cmd_tx, engine_handle) = runner::setup_engine(engine);
+        let submitter = runner::Submitter::new(cmd_tx, cli.precision);
+
+        let served = match cli.mode {
+            Mode::Serve => adapters::http::serve_api(&cli.listen, submitter).await,
+            _ => adapters::stream::serve_lines(&stream_addr(&cli), submitter).await,
+        };
+        if let Err(e) = served {
+            error!("Failed to listen for connections: {}", e);
+            ExitCode::Io.exit();
+        }
+
+        // The front end has shut down and dropped its senders, so the engine drains and stops.
+        let (state, _) = runner::finalize_engine(engine_handle)
+            .await
+            .unwrap_or_else(|e| engine_failed(e));
+        write_final_accounts(&cli, &state, &output);
+        return;
+    }
+
+    let file_path = cli
+        .input
+        .clone()
+        .expect("input path is required outside serve and stream");
+
+    let input_format = cli
+        .input_format
+        .unwrap_or_else(|| InputFormat::from_path(&file_path));
+
+    let input = open_input(&file_path).unwrap_or_else(|e| {
+        error!("Failed to open input file: {}", e);
+        ExitCode::Io.exit();
+    });
+
+    if cli.mode == Mode::Validate {
+        let report = match input_format {
+            InputFormat::Csv => {
+                let mut csv_reader = open_csv(input, cli.strict);
+                let records = adapters::csv_parser::read_transactions(&mut csv_reader);
+                validation::validate(records, &cli.precision, cli.strict)
+            }
+            InputFormat::Jsonl => {
+                let records = JsonlReader::new(BufReader::new(input), cli.strict);
+                validation::validate(records, &cli.precision, cli.strict)
+            }
+        };
+
+        print!("{}", report);
+        match report.is_valid() {
+            true => ExitCode::Success.exit(),
+            false => ExitCode::InvalidInput.exit(),
+        }
+    }
+
+    if cli.mode == Mode::Statement {
+        let client = cli.client.expect("statement requires a client");
+        let lines = match input_format {
+            InputFormat::Csv => {
+                let mut csv_reader = open_csv(input, cli.strict);
+                let records = adapters::csv_parser::read_transactions(&mut csv_reader);
+                statement::statement(records, client, &cli.precision, cli.strict)
+            }
+            InputFormat::Jsonl => {
+                let records = JsonlReader::new(BufReader::new(input), cli.strict);
+                statement::statement(records, client, &cli.precision, cli.strict)
+            }
+        };
+        let lines = lines.unwrap_or_else(|e| {
+            error!("Strict mode: input rejected at {}", e);
+            ExitCode::InvalidInput.exit();
+        });
+
+        let written = match &cli.output {
+            Some(path) => write_atomic(path, |writer| statement::write_statement(&lines, writer)),
+            None => statement::write_statement(&lines, io::stdout()),
+        };
+        if let Err(e) = written {
+            error!("Failed to write statement: {}", e);
+            ExitCode::Io.exit();
+        }
+        return;
+    }
+
+    if cli.mode == Mode::WhatIf {
+        let report = what_if(&cli, input, input_format);
+        let write = |writer: &mut dyn Write| match cli.diff_format {
+            DiffFormat::Human => write!(writer, "{}", report),
+            DiffFormat::Csv => report.write_csv(writer),
+        };
+        let written = match &cli.output {
+            Some(path) => write_atomic(path, |writer| write(writer)),
+            None => write(&mut io::stdout()),
+        };
+        if let Err(e) = written {
+            error!("Failed to write what-if report: {}", e);
+            ExitCode::Io.exit();
+        }
+        return;
+    }
+
+    let ingest = IngestOptions {
+        precision: cli.precision,
+        strict: cli.strict,
+    };
+
+    // Resume following from the last checkpoint, if one was saved by an earlier run
+    let checkpoint = cli.checkpoint.as_deref().and_then(|path| {
+        read_checkpoint(path).unwrap_or_else(|e| {
+            error!("Failed to read checkpoint {}: {}", path, e);
+            ExitCode::Io.exit();
+        })
+    });
+    let resume = checkpoint.as_ref().map(|checkpoint| checkpoint.position());
+    if let Some(position) = resume {
+        info!("Resuming after line {} of {}", position.line, file_path);
+    }
+
+    let engine = EngineOptions {
+        journal: open_journal(&cli, resume),
+        metrics: start_metrics(&cli).await,
+        state: checkpoint.map(|checkpoint| checkpoint.state),
+        snapshots: cli.follow.then(|| SnapshotOptions {
+            every: Duration::from_secs(cli.snapshot_every),
+            output: cli.output.clone().map(|path| (path, output.clone())),
+            checkpoint: cli.checkpoint.clone(),
+        }),
+    };
+
+    let started = Instant::now();
+    let (cmd_tx, engine_handle) = runner::setup_engine(engine);
+
+    let summary = match input_format {
+        _ if cli.follow => {
+            drop(input);
+            follow_input(&cli, &file_path, input_format, resume, cmd_tx, ingest).await
+        }
+        InputFormat::Csv => {
+            let mut csv_reader = open_csv(input, cli.strict);
+            let records = adapters::csv_parser::read_transactions(&mut csv_reader);
+            runner::send_commands_to_engine(records, cmd_tx, ingest).await
+        }
+        InputFormat::Jsonl => {
+            let records = JsonlReader::new(BufReader::new(input), cli.strict);
+            runner::send_commands_to_engine(records, cmd_tx, ingest).await
+        }
+    };
+
+    if let Some(e) = &summary.failure {
+        error!(
+            "Strict mode: input rejected at {} ({} valid rows before it, no balances written)",
+            e, summary.processed
+        );
+        // Let the engine drain so the journal is flushed with every command it applied
+        if let Err(e) = runner::finalize_engine(engine_handle).await {
+            engine_failed(e);
+        }
+        ExitCode::InvalidInput.exit();
+    }
+
+    let ingested = Instant::now();
+    let (state, stats) = runner::finalize_engine(engine_handle)
+        .await
+        .unwrap_or_else(|e| engine_failed(e));
+    let drained = Instant::now();
+
+    if cli.mode == Mode::Reconcile {
+        reconcile_accounts(&cli, &state);
+    }
+    write_final_accounts(&cli, &state, &output);
+
+    if let Some(path) = &cli.summary {
+        let timings = PhaseTimings {
+            ingest_ms: PhaseTimings::millis(ingested - started),
+            engine_ms: PhaseTimings::millis(drained - ingested),
+            output_ms: PhaseTimings::millis(drained.elapsed()),
+        };
+        let run_summary = RunSummary::new(&summary, &stats, &state, timings);
+        if let Err(e) = write_summary(path, &run_summary) {
+            error!("Failed to write summary file: {}", e);
+            ExitCode::Io.exit();
+        }
+    }
+}
+
+/// Build a CSV reader over `input`, exiting if strict mode rejects its header.
+fn open_csv<R: Read>(input: R, strict: bool) -> csv::Reader<R> {
+    let mut csv_reader = adapters::csv_parser::build_csv_reader(input, strict);
+    if let Some(Err(e)) = strict.then(|| adapters::csv_parser::check_header(&mut csv_reader)) {
+        error!("Strict mode: input rejected at {}", e);
+        ExitCode::InvalidInput.exit();
+    }
+    csv_reader
+}
+
+/// Send rows of `path` to the engine as they are appended, until Ctrl-C.
+/// When resuming, reading starts right after the row at `resume`, which the restored
+/// state already reflects.
+async fn follow_input(
+    cli: &CliArgs,
+    path: &str,
+    format: InputFormat,
+    resume: Option<Position>,
+    cmd_tx: Sender<EngineRequest>,
+    ingest: IngestOptions,
+) -> IngestSummary {
+    let file = File::open(path).unwrap_or_else(|e| {
+        error!("Failed to open input file: {}", e);
+        ExitCode::Io.exit();
+    });
+
+    // Stop on Ctrl-C, or when the engine has gone away and nothing more can be applied
+    let stop = Arc::new(AtomicBool::new(false));
+    tokio::spawn({
+        let stop = stop.clone();
+        let engine = cmd_tx.clone();
+        async move {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Stopping at the end of the data read so far");
+                }
+                () = engine.closed() => error!("Engine stopped, no longer following the input"),
+            }
+            stop.store(true, Ordering::Relaxed);
+        }
+    });
+    let input = FollowReader::new(file, stop);
+    let strict = cli.strict;
+
+    // The reader sleeps while waiting for data, so it gets a thread of its own and leaves the
+    // runtime's workers free to notice Ctrl-C.
+    let runtime = tokio::runtime::Handle::current();
+    let followed = tokio::task::spawn_blocking(move || {
+        runtime.block_on(read_followed(input, strict, format, resume, cmd_tx, ingest))
+    });
+    followed.await.unwrap_or_else(|e| {
+        error!("Input reader error: {:?}", e);
+        ExitCode::Internal.exit();
+    })
+}
+
+/// Parse a followed file and send its rows to the engine, starting after `resume`.
+async fn read_followed(
+    mut input: FollowReader<File>,
+    strict: bool,
+    format: InputFormat,
+    resume: Option<Position>,
+    cmd_tx: Sender<EngineRequest>,
+    ingest: IngestOptions,
+) -> IngestSummary {
+    let skip = usize::from(resume.is_some());
+
+    if let InputFormat::Csv = format {
+        let mut csv_reader = open_csv(input, strict);
+        if let Some(position) = resume {
+            // The header has to be read from the top before jumping past it
+            let mut start = csv::Position::new();
+            start
+                .set_byte(position.byte)
+                .set_line(position.line)
+                .set_record(position.line.saturating_sub(1));
+            if let Err(e) = csv_reader
+                .headers()
+                .cloned()
+                .and_then(|_| csv_reader.seek(start))
+            {
+                error!("Failed to resume input: {}", e);
+                ExitCode::Io.exit();
+            }
+        }
+        let records = adapters::csv_parser::read_transactions(&mut csv_reader).skip(skip);
+        return runner::send_commands_to_engine(records, cmd_tx, ingest).await;
+    }
+
+    if let Some(Err(e)) = resume.map(|position| input.seek(SeekFrom::Start(position.byte))) {
+        error!("Failed to resume input: {}", e);
+        ExitCode::Io.exit();
+    }
+
+    let reader = JsonlReader::new(BufReader::new(input), strict);
+    let reader = match resume {
+        Some(position) => reader.starting_at(position),
+        None => reader,
+    };
+    runner::send_commands_to_engine(reader.skip(skip), cmd_tx, ingest).await
+}
+
+/// Run the input, or the commands of a journal, under the current rules and the candidate
+/// policy.
+fn what_if(cli: &CliArgs, input: Box<dyn Read>, format: InputFormat) -> WhatIfReport {
+    let path = cli.policy.as_deref().expect("what-if requires a policy");
+    let policy = load_policy(path).unwrap_or_else(|e| {
+        error!("{}", e);
+        ExitCode::Usage.exit();
+    });
+
+    let report = |what_if: WhatIf| {
+        what_if.report(&cli.precision).unwrap_or_else(|e| {
+            error!("{}", e);
+            ExitCode::InvalidInput.exit();
+        })
+    };
+
+    if cli.from_journal {
+        let mut what_if = WhatIf::new(policy);
+        for cmd in journal_commands(BufReader::new(input)) {
+            let cmd = cmd.unwrap_or_else(|e| {
+                error!("{}", e);
+                ExitCode::InvalidInput.exit();
+            });
+            what_if.apply(cmd.row, cmd.command);
+        }
+        return report(what_if);
+    }
+
+    let what_if = match format {
+        InputFormat::Csv => {
+            let mut csv_reader = open_csv(input, cli.strict);
+            let records = adapters::csv_parser::read_transactions(&mut csv_reader);
+            whatif::what_if(records, policy, &cli.precision, cli.strict)
+        }
+        InputFormat::Jsonl => {
+            let records = JsonlReader::new(BufReader::new(input), cli.strict);
+            whatif::what_if(records, policy, &cli.precision, cli.strict)
+        }
+    };
+    report(what_if.unwrap_or_else(|e| {
+        error!("Strict mode: input rejected at {}", e);
+        ExitCode::InvalidInput.exit();
+    }))
+}
+
+/// Report why the engine produced no state and exit with the matching code.
+fn engine_failed(e: EngineError) -> ! {
+    error!("{}", e);
+    match e {
+        EngineError::Task(_) => ExitCode::Internal.exit(),
+        EngineError::Io(_) => ExitCode::Io.exit(),
+        EngineError::InvalidJournal(_) => ExitCode::InvalidInput.exit(),
+    }
+}
+
+/// Balances that break an engine invariant are never written or reported on.
+fn check_invariants(state: &State) {
+    if let Err(e) = state.check_invariants() {
+        error!("Invariant violated, no balances written: {}", e);
+        ExitCode::Invariant.exit();
+    }
+}
+
+/// Write the final balances to `--output`, or stdout when it is not given.
+fn write_final_accounts(cli: &CliArgs, state: &State, output: &OutputOptions) {
+    check_invariants(state);
+
+    let written = match &cli.output {
+        Some(path) => write_accounts_to_file(&state.accounts, output, path),
+        None => write_accounts(&state.accounts, output, io::stdout()),
+    };
+    if let Err(e) = written {
+        error!("Failed to write balances: {}", e);
+        ExitCode::Io.exit();
+    }
+}
+
+/// Compare the final balances with the expected accounts file, write the differences and exit
+/// with a status telling whether there were any.
+fn reconcile_accounts(cli: &CliArgs, state: &State) -> ! {
+    check_invariants(state);
+
+    let path = cli
+        .expected
+        .as_deref()
+        .expect("reconcile requires an expected file");
+    let expected = read_accounts_file(path).unwrap_or_else(|e| {
+        error!("{}", e);
+        ExitCode::Io.exit();
+    });
+    let actual = accounts_from_state(&state.accounts, &cli.precision);
+    let mismatches = reconcile::reconcile(&expected, &actual);
+
+    let written = match &cli.output {
+        Some(path) => write_atomic(path, |writer| {
+            reconcile::write_mismatches(&mismatches, writer)
+        }),
+        None => reconcile::write_mismatches(&mismatches, io::stdout()),
+    };
+    if let Err(e) = written {
+        error!("Failed to write reconciliation report: {}", e);
+        ExitCode::Io.exit();
+    }
+
+    if mismatches.is_empty() {
+        info!("All {} accounts match {}", expected.len(), path);
+        ExitCode::Success.exit();
+    }
+    let clients: BTreeSet<u16> = mismatches.iter().map(|m| m.client).collect();
+    error!("{} accounts differ from {}", clients.len(), path);
+    ExitCode::Mismatch.exit();
+}
+
+/// Create the journal file requested on the command line, if any, and its checkpoints file.
+/// When resuming after `resume`, both are kept up to that row and appended to instead.
+fn open_journal(cli: &CliArgs, resume: Option<Position>) -> Option<JournalWriter<BufWriter<File>>> {
+    cli.journal.as_ref().map(|path| {
+        let checkpoints_path = checkpoints_path(path);
+        let journal = match resume {
+            Some(position) => append_journal(path, &checkpoints_path, position.line, cli),
+            None => File::create(path).and_then(|file| {
+                let checkpoints = File::create(&checkpoints_path)?;
+                let (file, checkpoints) = (BufWriter::new(file), BufWriter::new(checkpoints));
+                Ok(JournalWriter::new(file, checkpoints, cli.checkpoint_every))
+            }),
+        };
+        journal.unwrap_or_else(|e| {
+            error!("Failed to create journal file: {}", e);
+            ExitCode::Io.exit();
+        })
+    })
+}
+
+/// Open a journal and its checkpoints for appending, dropping whatever they hold past `row`:
+/// those rows are read and journaled again by the resumed run.
+fn append_journal(
+    path: &str,
+    checkpoints_path: &str,
+    row: u64,
+    cli: &CliArgs,
+) -> io::Result<JournalWriter<BufWriter<File>>> {
+    let open = |path: &str| {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+    };
+
+    let file = open(path)?;
+    let (length, latest_timestamp) = journal_length_through(BufReader::new(&file), row)?;
+    file.set_len(length)?;
+
+    let checkpoints = open(checkpoints_path)?;
+    checkpoints.set_len(checkpoints_length_through(
+        BufReader::new(&checkpoints),
+        row,
+    )?)?;
+
+    let (file, checkpoints) = (BufWriter::new(file), BufWriter::new(checkpoints));
+    let journal = JournalWriter::new(file, checkpoints, cli.checkpoint_every);
+    Ok(journal.resuming(length, latest_timestamp))
+}
+
+/// Start serving metrics when an address was given on the command line.
+async fn start_metrics(cli: &CliArgs) -> Option<Arc<Metrics>> {
+    let addr = cli.metrics_addr.as_ref()?;
+    let metrics = Arc::new(Metrics::default());
+    let router = adapters::http::metrics_router(metrics.clone());
+
+    if let Err(e) = adapters::http::spawn_server(addr, router).await {
+        error!("Failed to serve metrics on {}: {}", addr, e);
+        ExitCode::Io.exit();
+    }
+
+    Some(metrics)
+}
+
+/// Socket the `stream` subcommand accepts lines on.
+fn stream_addr(cli: &CliArgs) -> StreamAddr {
+    match &cli.unix_socket {
+        #[cfg(unix)]
+        Some(path) => StreamAddr::Unix(path.into()),
+        #[cfg(not(unix))]
+        Some(_) => {
+            error!("Unix domain sockets are not supported on this platform");
+            ExitCode::Usage.exit();
+        }
+        None => StreamAddr::Tcp(cli.listen.clone()),
+    }
 }
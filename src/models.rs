@@ -1,3 +1,5 @@
 pub mod account;
 pub mod command;
+pub mod error;
+pub mod precision;
 pub mod transaction;
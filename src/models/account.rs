@@ -1,7 +1,8 @@
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 
 /// Represents a client account state.
-#[derive(serde::Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Account {
     pub client_id: u16,
 
@@ -12,4 +13,45 @@ pub struct Account {
     pub held: Decimal,
 
     pub locked: bool,
+
+    /// Why the account was locked, if it is.
+    #[serde(default)]
+    pub lock_reason: Option<LockReason>,
+
+    /// Number of deposits, withdrawals and disputes applied to the account.
+    #[serde(default)]
+    pub deposit_count: u32,
+    #[serde(default)]
+    pub withdrawal_count: u32,
+    #[serde(default)]
+    pub dispute_count: u32,
+}
+
+impl Account {
+    /// Empty, unlocked account for a client seen for the first time.
+    pub fn new(client_id: u16) -> Self {
+        Account {
+            client_id,
+            available: Decimal::ZERO,
+            held: Decimal::ZERO,
+            locked: false,
+            lock_reason: None,
+            deposit_count: 0,
+            withdrawal_count: 0,
+            dispute_count: 0,
+        }
+    }
+
+    /// Available plus held funds. The engine rejects commands that would take this out of
+    /// the range of `Decimal`.
+    pub fn total(&self) -> Decimal {
+        self.available + self.held
+    }
+}
+
+/// Event that froze an account.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "reason", rename_all = "lowercase")]
+pub enum LockReason {
+    Chargeback { tx: u32 },
 }
@@ -1,7 +1,9 @@
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 
 /// Represents high-level parsed commands from input.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
 pub enum Command {
     Deposit {
         client_id: u16,
@@ -26,3 +28,56 @@ pub enum Command {
         tx: u32,
     },
 }
+
+impl Command {
+    /// Lowercase transaction type, as written in the input.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Command::Deposit { .. } => "deposit",
+            Command::Withdrawal { .. } => "withdrawal",
+            Command::Dispute { .. } => "dispute",
+            Command::Resolve { .. } => "resolve",
+            Command::Chargeback { .. } => "chargeback",
+        }
+    }
+
+    pub fn client_id(&self) -> u16 {
+        match self {
+            Command::Deposit { client_id, .. }
+            | Command::Withdrawal { client_id, .. }
+            | Command::Dispute { client_id, .. }
+            | Command::Resolve { client_id, .. }
+            | Command::Chargeback { client_id, .. } => *client_id,
+        }
+    }
+
+    /// Id of the transaction created or referenced by this command.
+    pub fn tx(&self) -> u32 {
+        match self {
+            Command::Deposit { tx, .. }
+            | Command::Withdrawal { tx, .. }
+            | Command::Dispute { tx, .. }
+            | Command::Resolve { tx, .. }
+            | Command::Chargeback { tx, .. } => *tx,
+        }
+    }
+
+    /// Amount moved by a deposit or withdrawal; references to other transactions carry none.
+    pub fn amount(&self) -> Option<Decimal> {
+        match self {
+            Command::Deposit { amount, .. } | Command::Withdrawal { amount, .. } => Some(*amount),
+            _ => None,
+        }
+    }
+}
+
+/// A command tagged with the input line it came from and its optional timestamp.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequencedCommand {
+    pub row: u64,
+    /// Byte offset of the row in the input, to resume reading from after a restart.
+    #[serde(default)]
+    pub byte: u64,
+    pub timestamp: Option<u64>,
+    pub command: Command,
+}
@@ -0,0 +1,118 @@
+use rust_decimal::Decimal;
+use std::fmt;
+
+/// Location of a row in the input source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// 1-based line number; for CSV the header is line 1.
+    pub line: u64,
+    /// Byte offset of the start of the row.
+    pub byte: u64,
+}
+
+impl From<&csv::Position> for Position {
+    fn from(pos: &csv::Position) -> Self {
+        Position {
+            line: pos.line(),
+            byte: pos.byte(),
+        }
+    }
+}
+
+/// What is wrong with an input row.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputErrorKind {
+    /// The `type` column is not one of the known transaction types.
+    UnknownType(String),
+    /// Deposit or withdrawal without an amount.
+    MissingAmount(&'static str),
+    /// Dispute, resolve or chargeback carrying an amount.
+    UnexpectedAmount(&'static str),
+    /// Deposit or withdrawal with a zero or negative amount.
+    NonPositiveAmount { kind: &'static str, amount: Decimal },
+    /// Amount with more decimal places than allowed.
+    ExcessPrecision { amount: Decimal, scale: u32 },
+    /// Amount or timestamp that is not a valid number.
+    BadNumber(String),
+    /// Client or transaction id that is not a valid id.
+    BadId(String),
+    /// Row that could not be read at all, e.g. invalid JSON or UTF-8.
+    Malformed(String),
+    /// Header does not match the expected schema (strict mode).
+    BadHeader(String),
+    /// Row with a different number of fields than the header (strict mode).
+    WrongFieldCount { expected: u64, found: u64 },
+    /// JSON field that is not part of the schema (strict mode).
+    UnknownField(String),
+}
+
+impl InputErrorKind {
+    /// Stable snake_case identifier, for logs and reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InputErrorKind::UnknownType(_) => "unknown_type",
+            InputErrorKind::MissingAmount(_) => "missing_amount",
+            InputErrorKind::UnexpectedAmount(_) => "unexpected_amount",
+            InputErrorKind::NonPositiveAmount { .. } => "non_positive_amount",
+            InputErrorKind::ExcessPrecision { .. } => "excess_precision",
+            InputErrorKind::BadNumber(_) => "bad_number",
+            InputErrorKind::BadId(_) => "bad_id",
+            InputErrorKind::Malformed(_) => "malformed",
+            InputErrorKind::BadHeader(_) => "bad_header",
+            InputErrorKind::WrongFieldCount { .. } => "wrong_field_count",
+            InputErrorKind::UnknownField(_) => "unknown_field",
+        }
+    }
+}
+
+impl fmt::Display for InputErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputErrorKind::UnknownType(kind) => write!(f, "Unknown transaction type: {}", kind),
+            InputErrorKind::MissingAmount(kind) => write!(f, "Missing amount in {}", kind),
+            InputErrorKind::UnexpectedAmount(kind) => write!(f, "Unexpected amount in {}", kind),
+            InputErrorKind::NonPositiveAmount { kind, amount } => {
+                write!(f, "Amount must be positive in {}: {}", kind, amount)
+            }
+            InputErrorKind::ExcessPrecision { amount, scale } => {
+                write!(
+                    f,
+                    "Amount {} has more than {} decimal places",
+                    amount, scale
+                )
+            }
+            InputErrorKind::BadNumber(msg) => write!(f, "Invalid number: {}", msg),
+            InputErrorKind::BadId(msg) => write!(f, "Invalid id: {}", msg),
+            InputErrorKind::Malformed(msg) => write!(f, "Malformed row: {}", msg),
+            InputErrorKind::BadHeader(msg) => write!(f, "Invalid header: {}", msg),
+            InputErrorKind::WrongFieldCount { expected, found } => {
+                write!(f, "Expected {} fields, found {}", expected, found)
+            }
+            InputErrorKind::UnknownField(name) => write!(f, "Unknown field: {}", name),
+        }
+    }
+}
+
+/// An input problem together with where it occurred, when known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputError {
+    pub kind: InputErrorKind,
+    pub position: Option<Position>,
+}
+
+impl InputError {
+    pub fn new(kind: InputErrorKind, position: Option<Position>) -> Self {
+        InputError { kind, position }
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(pos) => write!(f, "line {}, byte {}: {}", pos.line, pos.byte, self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
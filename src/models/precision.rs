@@ -0,0 +1,134 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::str::FromStr;
+
+use crate::models::error::InputErrorKind;
+
+/// Number of decimal places required by the spec for amounts and balances.
+pub const DEFAULT_SCALE: u32 = 4;
+
+/// What to do with input amounts that carry more decimal places than allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Rounding {
+    /// Reject the row.
+    #[default]
+    Reject,
+    /// Round half to even (banker's rounding).
+    HalfEven,
+    /// Round half away from zero.
+    HalfUp,
+    /// Truncate towards zero.
+    Down,
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(Rounding::Reject),
+            "half-even" => Ok(Rounding::HalfEven),
+            "half-up" => Ok(Rounding::HalfUp),
+            "down" => Ok(Rounding::Down),
+            _ => Err(format!("Unknown rounding mode: {}", s)),
+        }
+    }
+}
+
+/// Fixed decimal precision applied to input amounts and output balances.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Precision {
+    pub scale: u32,
+    pub rounding: Rounding,
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Precision {
+            scale: DEFAULT_SCALE,
+            rounding: Rounding::default(),
+        }
+    }
+}
+
+impl Precision {
+    /// Bring an input amount within the allowed scale, rounding or rejecting it as configured.
+    /// Trailing zeros do not count, so `1.50000` is accepted at scale 4.
+    pub fn apply(&self, amount: Decimal) -> Result<Decimal, InputErrorKind> {
+        if amount.normalize().scale() <= self.scale {
+            return Ok(amount);
+        }
+
+        let strategy = match self.rounding {
+            Rounding::Reject => {
+                return Err(InputErrorKind::ExcessPrecision {
+                    amount,
+                    scale: self.scale,
+                });
+            }
+            Rounding::HalfEven => RoundingStrategy::MidpointNearestEven,
+            Rounding::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            Rounding::Down => RoundingStrategy::ToZero,
+        };
+
+        Ok(amount.round_dp_with_strategy(self.scale, strategy))
+    }
+
+    /// Format a balance with exactly `scale` decimal places.
+    pub fn normalize(&self, amount: Decimal) -> Decimal {
+        let mut normalized =
+            amount.round_dp_with_strategy(self.scale, RoundingStrategy::MidpointNearestEven);
+        normalized.rescale(self.scale);
+        normalized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_apply_rounding_modes() {
+        let reject = Precision::default();
+        assert_eq!(reject.apply(dec("1.2345")), Ok(dec("1.2345")));
+        assert_eq!(reject.apply(dec("1.50000")), Ok(dec("1.50000")));
+        assert_eq!(
+            reject.apply(dec("1.23456")),
+            Err(InputErrorKind::ExcessPrecision {
+                amount: dec("1.23456"),
+                scale: 4
+            })
+        );
+
+        let with = |rounding| Precision { scale: 4, rounding };
+        assert_eq!(
+            with(Rounding::HalfEven).apply(dec("1.00005")),
+            Ok(dec("1.0000"))
+        );
+        assert_eq!(
+            with(Rounding::HalfUp).apply(dec("1.00005")),
+            Ok(dec("1.0001"))
+        );
+        assert_eq!(
+            with(Rounding::Down).apply(dec("1.99999")),
+            Ok(dec("1.9999"))
+        );
+    }
+
+    #[test]
+    fn test_normalize_to_fixed_scale() {
+        let precision = Precision::default();
+        assert_eq!(precision.normalize(dec("13.0")).to_string(), "13.0000");
+        assert_eq!(precision.normalize(Decimal::ZERO).to_string(), "0.0000");
+        assert_eq!(precision.normalize(dec("-0.5")).to_string(), "-0.5000");
+
+        let two = Precision {
+            scale: 2,
+            rounding: Rounding::Reject,
+        };
+        assert_eq!(two.normalize(dec("1.005")).to_string(), "1.00");
+    }
+}
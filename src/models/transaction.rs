@@ -1,7 +1,13 @@
 use rust_decimal::Decimal;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::models::command::Command;
+use crate::models::{command::Command, error::InputErrorKind, precision::Precision};
+
+/// Columns every input must have, in this order.
+pub const REQUIRED_COLUMNS: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// Optional columns that may follow the required ones.
+pub const EXTENSION_COLUMNS: [&str; 1] = ["timestamp"];
 
 /// CSV input record with optional amount field.
 /// Uses direct Decimal deserialization for clarity.
@@ -17,14 +23,18 @@ pub struct TransactionInput {
 
     #[serde(default, with = "rust_decimal::serde::str_option")]
     amount: Option<Decimal>,
+
+    /// Optional extension column: seconds since the Unix epoch.
+    #[serde(default)]
+    pub timestamp: Option<u64>,
 }
 
 impl TransactionInput {
     /// Converts TransactionInput into a Command, validating required fields.
-    pub fn to_command(&self) -> Result<Command, String> {
+    pub fn to_command(&self) -> Result<Command, InputErrorKind> {
         match self.kind.as_str() {
             "deposit" => {
-                let amount = self.amount.ok_or("Missing amount in deposit")?;
+                let amount = self.positive_amount("deposit")?;
                 Ok(Command::Deposit {
                     client_id: self.client_id,
                     tx: self.tx,
@@ -32,7 +42,7 @@ impl TransactionInput {
                 })
             }
             "withdrawal" => {
-                let amount = self.amount.ok_or("Missing amount in withdrawal")?;
+                let amount = self.positive_amount("withdrawal")?;
                 Ok(Command::Withdrawal {
                     client_id: self.client_id,
                     tx: self.tx,
@@ -51,13 +61,67 @@ impl TransactionInput {
                 client_id: self.client_id,
                 tx: self.tx,
             }),
-            _ => Err(format!("Unknown transaction type: {}", self.kind)),
+            _ => Err(InputErrorKind::UnknownType(self.kind.clone())),
+        }
+    }
+
+    /// Converts into a Command whose amount has been brought within the given precision.
+    /// An amount that rounds to zero is rejected like a zero amount.
+    pub fn to_command_with_precision(
+        &self,
+        precision: &Precision,
+    ) -> Result<Command, InputErrorKind> {
+        let mut cmd = self.to_command()?;
+        let kind = cmd.kind();
+
+        if let Command::Deposit { amount, .. } | Command::Withdrawal { amount, .. } = &mut cmd {
+            *amount = precision.apply(*amount)?;
+            if *amount <= Decimal::ZERO {
+                return Err(InputErrorKind::NonPositiveAmount {
+                    kind,
+                    amount: *amount,
+                });
+            }
+        }
+
+        Ok(cmd)
+    }
+
+    /// Like `to_command_with_precision`, but an amount on a dispute, resolve or chargeback
+    /// is an error rather than ignored, as in strict mode.
+    pub fn to_strict_command(&self, precision: &Precision) -> Result<Command, InputErrorKind> {
+        if let Some(kind) = self.unexpected_amount() {
+            return Err(kind);
+        }
+        self.to_command_with_precision(precision)
+    }
+
+    /// Dispute, resolve and chargeback rows reference an earlier transaction and take no amount.
+    /// Reports the row type when one is present anyway.
+    pub fn unexpected_amount(&self) -> Option<InputErrorKind> {
+        self.amount?;
+
+        match self.kind.as_str() {
+            "dispute" => Some(InputErrorKind::UnexpectedAmount("dispute")),
+            "resolve" => Some(InputErrorKind::UnexpectedAmount("resolve")),
+            "chargeback" => Some(InputErrorKind::UnexpectedAmount("chargeback")),
+            _ => None,
         }
     }
+
+    fn positive_amount(&self, kind: &'static str) -> Result<Decimal, InputErrorKind> {
+        let amount = self.amount.ok_or(InputErrorKind::MissingAmount(kind))?;
+
+        if amount <= Decimal::ZERO {
+            return Err(InputErrorKind::NonPositiveAmount { kind, amount });
+        }
+
+        Ok(amount)
+    }
 }
 
 /// Internal record of a transaction for dispute resolution.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionRecord {
     pub client_id: u16,
     pub amount: Decimal,
@@ -65,7 +129,7 @@ pub struct TransactionRecord {
     pub status: TransactionStatus,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum TransactionStatus {
     Normal,
     Disputed,
@@ -73,6 +137,17 @@ pub enum TransactionStatus {
     // TODO: add rejected ?
 }
 
+impl TransactionStatus {
+    /// Stable snake_case identifier, for reports and APIs.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionStatus::Normal => "normal",
+            TransactionStatus::Disputed => "disputed",
+            TransactionStatus::ChargedBack => "charged_back",
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,6 +160,7 @@ mod tests {
             client_id: client,
             tx,
             amount,
+            timestamp: None,
         }
     }
 
@@ -161,18 +237,95 @@ mod tests {
         let deposit_missing_amount = make_input("deposit", 1, 60, None);
         let res = deposit_missing_amount.to_command();
         assert!(res.is_err());
-        assert_eq!(res.err().unwrap(), "Missing amount in deposit");
+        assert_eq!(res.err().unwrap(), InputErrorKind::MissingAmount("deposit"));
 
         // Missing amount for withdrawal
         let withdrawal_missing_amount = make_input("withdrawal", 2, 70, None);
         let res = withdrawal_missing_amount.to_command();
         assert!(res.is_err());
-        assert_eq!(res.err().unwrap(), "Missing amount in withdrawal");
+        assert_eq!(
+            res.err().unwrap().to_string(),
+            "Missing amount in withdrawal"
+        );
+
+        // Zero or negative amounts
+        let zero_deposit = make_input("deposit", 1, 61, Some(Decimal::ZERO));
+        assert_eq!(
+            zero_deposit.to_command().err().unwrap(),
+            InputErrorKind::NonPositiveAmount {
+                kind: "deposit",
+                amount: Decimal::ZERO
+            }
+        );
+        let negative_withdrawal = make_input("withdrawal", 2, 71, Some(Decimal::new(-5, 1)));
+        assert_eq!(
+            negative_withdrawal.to_command().err().unwrap().to_string(),
+            "Amount must be positive in withdrawal: -0.5"
+        );
 
         // Unknown command type
         let unknown = make_input("foobar", 3, 80, None);
         let res = unknown.to_command();
         assert!(res.is_err());
-        assert_eq!(res.err().unwrap(), "Unknown transaction type: foobar");
+        assert_eq!(
+            res.err().unwrap(),
+            InputErrorKind::UnknownType("foobar".into())
+        );
+
+        // Amount on a row that references another transaction
+        let dispute_with_amount = make_input("dispute", 4, 90, Some(Decimal::ONE));
+        assert!(dispute_with_amount.to_command().is_ok());
+        assert_eq!(
+            dispute_with_amount.unexpected_amount(),
+            Some(InputErrorKind::UnexpectedAmount("dispute"))
+        );
+        assert_eq!(
+            make_input("deposit", 4, 91, Some(Decimal::ONE)).unexpected_amount(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_command_parsing_applies_precision() {
+        use crate::models::precision::Rounding;
+
+        let deposit = make_input("deposit", 1, 90, Some(Decimal::new(123456, 5))); // 1.23456
+        assert_eq!(
+            deposit
+                .to_command_with_precision(&Precision::default())
+                .err()
+                .unwrap(),
+            InputErrorKind::ExcessPrecision {
+                amount: Decimal::new(123456, 5),
+                scale: 4
+            }
+        );
+
+        let rounding = Precision {
+            scale: 4,
+            rounding: Rounding::HalfUp,
+        };
+        match deposit.to_command_with_precision(&rounding).unwrap() {
+            Command::Deposit { amount, .. } => assert_eq!(amount, Decimal::new(12346, 4)),
+            _ => panic!("Expected deposit"),
+        }
+
+        // Rounding to zero leaves nothing to deposit
+        let tiny = make_input("deposit", 1, 91, Some(Decimal::new(1, 5))); // 0.00001
+        assert!(matches!(
+            tiny.to_command_with_precision(&rounding),
+            Err(InputErrorKind::NonPositiveAmount {
+                kind: "deposit",
+                ..
+            })
+        ));
+
+        // Rows without an amount are unaffected
+        let dispute = make_input("dispute", 1, 90, None);
+        assert!(
+            dispute
+                .to_command_with_precision(&Precision::default())
+                .is_ok()
+        );
     }
 }
@@ -8,7 +8,20 @@ fn test_sample_transactions() {
     cmd.arg("tests/data/sample_transactions.csv")
         .assert()
         .success()
-        .stdout(predicate::str::contains("1,5.5,0,5.5,false"));
+        .stdout(predicate::str::contains("1,5.5000,0.0000,5.5000,false"));
+}
+
+#[test]
+fn test_jsonl_input_by_extension() {
+    let mut cmd = Command::cargo_bin("payments_engine").unwrap();
+
+    cmd.arg("tests/data/sample_transactions.jsonl")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1,5.5000,0.0000,5.5000,false"))
+        .stderr(predicate::str::contains(
+            "Skipping invalid input line: line 3",
+        ));
 }
 
 #[test]
@@ -18,7 +31,28 @@ fn test_dispute_flow() {
     cmd.arg("tests/data/dispute_flow.csv")
         .assert()
         .success()
-        .stdout(predicate::str::contains("2,20.0,0.0,20.0,false"));
+        .stdout(predicate::str::contains("2,20.0000,0.0000,20.0000,false"));
+}
+
+#[test]
+fn test_dispute_flow_gzip() {
+    let mut cmd = Command::cargo_bin("payments_engine").unwrap();
+
+    cmd.arg("tests/data/dispute_flow.csv.gz")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2,20.0000,0.0000,20.0000,false"));
+}
+
+#[test]
+fn test_stdin_input() {
+    let mut cmd = Command::cargo_bin("payments_engine").unwrap();
+
+    cmd.arg("-")
+        .write_stdin(std::fs::read("tests/data/sample_transactions.csv").unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1,5.5000,0.0000,5.5000,false"));
 }
 
 #[test]
@@ -28,7 +62,102 @@ fn test_chargeback_flow() {
     cmd.arg("tests/data/chargeback_flow.csv")
         .assert()
         .success()
-        .stdout(predicate::str::contains("3,0.0,0.0,0.0,true"));
+        .stdout(predicate::str::contains("3,0.0000,0.0000,0.0000,true"));
+}
+
+#[test]
+fn test_chargeback_flow_jsonl_output() {
+    let mut cmd = Command::cargo_bin("payments_engine").unwrap();
+
+    cmd.args(["tests/data/chargeback_flow.csv", "--output-format", "jsonl"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"{"client":3,"available":"0.0000","held":"0.0000","total":"0.0000","locked":true,"lock_reason":{"reason":"chargeback","tx":300},"deposit_count":1,"withdrawal_count":0,"dispute_count":1}"#,
+        ));
+}
+
+#[test]
+fn test_output_is_sorted() {
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .arg("tests/data/multi_client.csv")
+        .assert()
+        .success()
+        .stdout(
+            "client,available,held,total,locked\n\
+             1,0.0000,0.0000,0.0000,true\n\
+             2,3.0000,0.0000,3.0000,false\n\
+             3,6.0000,0.0000,6.0000,false\n",
+        );
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["tests/data/multi_client.csv", "--sort", "total"])
+        .assert()
+        .success()
+        .stdout(
+            "client,available,held,total,locked\n\
+             3,6.0000,0.0000,6.0000,false\n\
+             2,3.0000,0.0000,3.0000,false\n\
+             1,0.0000,0.0000,0.0000,true\n",
+        );
+}
+
+#[test]
+fn test_deposit_overflowing_total_is_rejected() {
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["-", "--sort", "total"])
+        .write_stdin(
+            "type,client,tx,amount\n\
+             deposit,1,1,79228162514264337593543950335\n\
+             dispute,1,1,\n\
+             deposit,1,2,79228162514264337593543950335\n",
+        )
+        .assert()
+        .success()
+        .stdout(
+            "client,available,held,total,locked\n\
+             1,0.0000,79228162514264337593543950335,79228162514264337593543950335,false\n",
+        );
+}
+
+#[test]
+fn test_excess_precision_rejected_or_rounded() {
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .arg("tests/data/excess_precision.csv")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("7,1.2345,0.0000,1.2345,false"))
+        .stderr(predicate::str::contains("has more than 4 decimal places"));
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["tests/data/excess_precision.csv", "--rounding", "half-up"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("7,2.4691,0.0000,2.4691,false"));
+}
+
+#[test]
+fn test_invalid_rows_are_reported_with_position() {
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .arg("tests/data/invalid_rows.csv")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("8,0.0000,4.0000,4.0000,false"))
+        .stderr(predicate::str::contains(
+            "line 3, byte 40: Invalid id: client: eight",
+        ))
+        .stderr(predicate::str::contains(
+            "line 4, byte 62: Unknown transaction type: refund",
+        ))
+        .stderr(predicate::str::contains(
+            "Ignoring amount: line 5, byte 79: Unexpected amount in dispute",
+        ));
 }
 
 #[test]
@@ -48,7 +177,7 @@ fn test_duplicate_tx_ids() {
     cmd.arg("tests/data/duplicate_tx_ids.csv")
         .assert()
         .success()
-        .stdout(predicate::str::contains("5,30.0,0,30.0,false"));
+        .stdout(predicate::str::contains("5,30.0000,0.0000,30.0000,false"));
 }
 
 #[test]
@@ -83,3 +212,553 @@ chargeback,42,100,
     // Clean up test file
     std::fs::remove_file(input_path).unwrap();
 }
+
+#[test]
+fn test_journal_point_in_time_query() {
+    let journal_path = std::env::temp_dir().join("payments_engine_journal_flow.jsonl");
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["tests/data/journal_flow.csv", "--journal"])
+        .arg(&journal_path)
+        .args(["--checkpoint-every", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("6,2.0000,10.0000,12.0000,false"));
+
+    // Checkpoints are written next to the journal rather than into it
+    let journal = std::fs::read_to_string(&journal_path).unwrap();
+    assert!(
+        journal
+            .lines()
+            .all(|line| line.starts_with(r#"{"command":"#))
+    );
+    let checkpoints = std::fs::read_to_string(journal_path.with_extension("jsonl.checkpoints"));
+    assert_eq!(checkpoints.unwrap().lines().count(), 2);
+
+    // After input line 3 only the two deposits have been applied
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .arg("replay")
+        .arg(&journal_path)
+        .args(["--at-row", "3", "--client", "6"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("6,15.0000,0.0000,15.0000,false"));
+
+    // By timestamp, the withdrawal is included but not the dispute
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .arg("replay")
+        .arg(&journal_path)
+        .args(["--at-time", "1700000250"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("6,12.0000,0.0000,12.0000,false"));
+
+    std::fs::remove_file(journal_path.with_extension("jsonl.checkpoints")).unwrap();
+    std::fs::remove_file(journal_path).unwrap();
+}
+
+#[test]
+fn test_strict_mode_fails_fast() {
+    let mut cmd = Command::cargo_bin("payments_engine").unwrap();
+
+    cmd.args(["tests/data/strict_violation.csv", "--strict"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains(
+            "Strict mode: input rejected at line 3, byte 38: Unexpected amount in dispute (1 valid rows before it",
+        ));
+
+    // Commands applied before the failure are still journaled
+    let journal_path = std::env::temp_dir().join("payments_engine_strict_journal.jsonl");
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["tests/data/strict_violation.csv", "--strict", "--journal"])
+        .arg(&journal_path)
+        .assert()
+        .code(4);
+    let journal = std::fs::read_to_string(&journal_path).unwrap();
+    assert_eq!(journal.lines().count(), 1);
+    assert!(journal.contains(r#""type":"deposit""#), "{}", journal);
+    std::fs::remove_file(journal_path.with_extension("jsonl.checkpoints")).unwrap();
+    std::fs::remove_file(journal_path).unwrap();
+
+    let mut cmd = Command::cargo_bin("payments_engine").unwrap();
+
+    cmd.args(["tests/data/strict_bad_header.csv", "--strict"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains(
+            "Invalid header: unknown column memo",
+        ));
+
+    let mut cmd = Command::cargo_bin("payments_engine").unwrap();
+
+    cmd.arg("tests/data/strict_violation.csv")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1,0.0000,2.0000,2.0000,false"));
+}
+
+#[test]
+fn test_validate_reports_without_balances() {
+    let mut cmd = Command::cargo_bin("payments_engine").unwrap();
+
+    cmd.args(["validate", "tests/data/duplicate_tx_ids.csv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Duplicate tx ids: 500, 501\n"))
+        .stdout(predicate::str::contains("false").not());
+
+    let mut cmd = Command::cargo_bin("payments_engine").unwrap();
+
+    cmd.args(["validate", "tests/data/invalid_rows.csv"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("  unknown_type: 1\n"))
+        .stdout(predicate::str::contains("  bad_id: 1\n"));
+}
+
+#[test]
+fn test_json_summary_file() {
+    let summary_path = std::env::temp_dir().join("payments_engine_summary.json");
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["tests/data/chargeback_flow.csv", "--summary"])
+        .arg(&summary_path)
+        .assert()
+        .success();
+
+    let summary: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&summary_path).unwrap()).unwrap();
+    assert_eq!(summary["commands_by_type"]["chargeback"], 1);
+    assert_eq!(summary["accounts_locked"], 1);
+    assert!(summary["timings"]["engine_ms"].is_number());
+}
+
+#[test]
+fn test_json_logs_with_command_spans() {
+    let mut cmd = Command::cargo_bin("payments_engine").unwrap();
+
+    cmd.args(["tests/data/chargeback_flow.csv", "--log-format", "json"])
+        .env("RUST_LOG", "debug")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            r#""span":{"client":3,"outcome":"applied","row":4,"tx":300,"type":"chargeback","name":"command"}"#,
+        ));
+}
+
+#[test]
+fn test_stream_mode_acknowledges_lines() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    // Reserve a free port, then hand it to the engine
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let addr = format!("127.0.0.1:{}", port);
+
+    let mut engine = std::process::Command::new(assert_cmd::cargo::cargo_bin("payments_engine"))
+        .args(["stream", "--listen", &addr])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let stream = (0..100)
+        .find_map(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            TcpStream::connect(&addr).ok()
+        })
+        .expect("engine did not start listening");
+
+    let mut writer = stream.try_clone().unwrap();
+    writer
+        .write_all(b"deposit,1,1,10.0\nwithdrawal,1,2,20.0\nrefund,1,3,1.0\n")
+        .unwrap();
+
+    let acks: Vec<String> = BufReader::new(stream)
+        .lines()
+        .take(3)
+        .map(Result::unwrap)
+        .collect();
+    engine.kill().unwrap();
+    let _ = engine.wait();
+
+    assert_eq!(
+        acks,
+        [
+            "ok",
+            "rejected insufficient_funds",
+            "invalid unknown_type Unknown transaction type: refund"
+        ]
+    );
+}
+
+#[test]
+fn test_follow_mode_snapshots_and_resumes() {
+    use std::io::Write;
+    use std::time::Duration;
+
+    let dir = std::env::temp_dir();
+    let input = dir.join("payments_engine_follow_input.csv");
+    let output = dir.join("payments_engine_follow_output.csv");
+    let checkpoint = dir.join("payments_engine_follow.ckpt");
+    let _ = std::fs::remove_file(&output);
+    let _ = std::fs::remove_file(&checkpoint);
+    std::fs::write(&input, "type,client,tx,amount\ndeposit,1,1,10.0\n").unwrap();
+
+    let follow = || {
+        std::process::Command::new(assert_cmd::cargo::cargo_bin("payments_engine"))
+            .arg(&input)
+            .args(["--follow", "--snapshot-every", "1", "--output"])
+            .arg(&output)
+            .arg("--checkpoint")
+            .arg(&checkpoint)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap()
+    };
+    let wait_for_output = |expected: &str| {
+        (0..100).any(|_| {
+            std::thread::sleep(Duration::from_millis(100));
+            std::fs::read_to_string(&output).is_ok_and(|out| out.contains(expected))
+        })
+    };
+    let append = |rows: &str| {
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&input)
+            .unwrap();
+        file.write_all(rows.as_bytes()).unwrap();
+    };
+
+    let mut engine = follow();
+    assert!(wait_for_output("1,10.0000,0.0000,10.0000,false"));
+    append("deposit,1,2,5.0\n");
+    assert!(wait_for_output("1,15.0000,0.0000,15.0000,false"));
+    // Crash without a chance to clean up; the checkpoint already covers both rows
+    engine.kill().unwrap();
+    let _ = engine.wait();
+
+    append("withdrawal,1,3,3.0\n");
+    let mut engine = follow();
+    let resumed = wait_for_output("1,12.0000,0.0000,12.0000,false");
+    engine.kill().unwrap();
+    let _ = engine.wait();
+    assert!(
+        resumed,
+        "rows before the checkpoint must not be applied twice"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_follow_mode_stops_on_ctrl_c() {
+    use std::time::Duration;
+
+    let dir = std::env::temp_dir();
+    let input = dir.join("payments_engine_follow_stop.csv");
+    let output = dir.join("payments_engine_follow_stop_output.csv");
+    let _ = std::fs::remove_file(&output);
+    std::fs::write(&input, "type,client,tx,amount\ndeposit,1,1,10.0\n").unwrap();
+
+    let mut engine = std::process::Command::new(assert_cmd::cargo::cargo_bin("payments_engine"))
+        .arg(&input)
+        .args(["--follow", "--output"])
+        .arg(&output)
+        .env("TOKIO_WORKER_THREADS", "1")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    // Give the reader time to reach the end of the file and start waiting for more
+    std::thread::sleep(Duration::from_millis(500));
+
+    std::process::Command::new("kill")
+        .args(["-INT", &engine.id().to_string()])
+        .status()
+        .unwrap();
+    let stopped = (0..50).find_map(|_| {
+        std::thread::sleep(Duration::from_millis(100));
+        engine.try_wait().unwrap()
+    });
+    if stopped.is_none() {
+        engine.kill().unwrap();
+    }
+    let _ = engine.wait();
+
+    assert!(stopped.is_some_and(|status| status.success()));
+    let written = std::fs::read_to_string(&output).unwrap();
+    assert!(written.contains("1,10.0000,0.0000,10.0000,false"));
+}
+
+#[test]
+fn test_statement_for_one_client() {
+    let mut cmd = Command::cargo_bin("payments_engine").unwrap();
+
+    cmd.args([
+        "statement",
+        "tests/data/chargeback_flow.csv",
+        "--client",
+        "3",
+    ])
+    .assert()
+    .success()
+    .stdout(predicate::str::starts_with(
+        "row,type,tx,amount,outcome,available,held,total,locked\n\
+             2,deposit,300,30.0000,applied,30.0000,0.0000,30.0000,false\n",
+    ))
+    .stdout(predicate::str::contains(
+        "4,chargeback,300,,applied,0.0000,0.0000,0.0000,true",
+    ));
+}
+
+#[test]
+fn test_exit_codes() {
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .arg("tests/data/missing.csv")
+        .assert()
+        .code(3);
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["tests/data/strict_violation.csv", "--strict"])
+        .assert()
+        .code(4);
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["validate", "tests/data/invalid_rows.csv"])
+        .assert()
+        .code(4);
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["tests/data/sample_transactions.csv", "--sort", "random"])
+        .assert()
+        .code(2);
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("statement"))
+        .stdout(predicate::str::contains("Exit codes:"));
+}
+
+#[test]
+fn test_config_file_and_output_path() {
+    let dir = std::env::temp_dir();
+    let config = dir.join("payments_engine_config.toml");
+    let output = dir.join("payments_engine_config_output.jsonl");
+    std::fs::write(&config, "output-format = \"jsonl\"\nscale = 2\n").unwrap();
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args([
+            "process",
+            "--input",
+            "tests/data/chargeback_flow.csv",
+            "--config",
+        ])
+        .arg(&config)
+        .arg("--output")
+        .arg(&output)
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+
+    let written = std::fs::read_to_string(&output).unwrap();
+    assert!(written.contains("\"client\":3,\"available\":\"0.00\""));
+    std::fs::remove_file(config).unwrap();
+    std::fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn test_output_file_is_never_partial() {
+    let dir = std::env::temp_dir();
+    let output = dir.join("payments_engine_atomic_accounts.csv");
+    let _ = std::fs::remove_file(&output);
+
+    // A strict failure writes nothing, not even an empty file
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["tests/data/strict_violation.csv", "--strict", "--output"])
+        .arg(&output)
+        .assert()
+        .code(4);
+    assert!(!output.exists());
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["tests/data/chargeback_flow.csv", "--output"])
+        .arg(&output)
+        .assert()
+        .success();
+    assert!(
+        std::fs::read_to_string(&output)
+            .unwrap()
+            .contains("3,0.0000,0.0000,0.0000,true")
+    );
+    std::fs::remove_file(&output).unwrap();
+
+    // An unwritable destination is an I/O failure
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["tests/data/chargeback_flow.csv", "--output"])
+        .arg(dir.join("payments_engine_missing_dir").join("accounts.csv"))
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Failed to write balances"));
+}
+
+#[test]
+fn test_reconcile_against_expected_balances() {
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["reconcile", "tests/data/multi_client.csv"])
+        .args(["--expected", "tests/data/multi_client_expected.csv"])
+        .assert()
+        .success()
+        .stdout("client,field,expected,actual\n");
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["reconcile", "tests/data/multi_client.csv"])
+        .args(["--expected", "tests/data/multi_client_bank.csv"])
+        .assert()
+        .code(6)
+        .stdout(
+            "client,field,expected,actual\n\
+             1,locked,false,true\n\
+             3,available,7,6.0000\n\
+             3,total,7,6.0000\n\
+             4,account,present,missing\n",
+        )
+        .stderr(predicate::str::contains("3 accounts differ"));
+}
+
+#[test]
+fn test_diff_two_account_files() {
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["diff", "tests/data/multi_client_expected.csv"])
+        .arg("tests/data/multi_client_bank.csv")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "  + client 4: available +1.0000, held 0.0000, total +1.0000\n",
+        ))
+        .stdout(predicate::str::contains("locked true -> false"));
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["diff", "tests/data/multi_client_expected.csv"])
+        .args(["tests/data/multi_client_bank.csv", "--format", "csv"])
+        .assert()
+        .success()
+        .stdout(
+            "client,change,available_delta,held_delta,total_delta,locked_before,locked_after\n\
+             1,changed,0.0000,0.0000,0.0000,true,false\n\
+             3,changed,1.0000,0.0000,1.0000,false,false\n\
+             4,new,1.0000,0.0000,1.0000,,false\n",
+        );
+}
+
+#[test]
+fn test_what_if_under_candidate_policy() {
+    let expected = "row,type,client,tx,amount,current,candidate\n\
+                    4,dispute,1,1,,applied,insufficient_funds\n\
+                    5,chargeback,1,1,,applied,not_disputed\n\
+                    6,deposit,1,3,5.0000,account_locked,applied\n";
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["what-if", "tests/data/dispute_after_withdrawal.csv"])
+        .args(["--policy", "tests/data/no_negative_available.toml"])
+        .args(["--format", "csv"])
+        .assert()
+        .success()
+        .stdout(expected);
+
+    let journal_path = std::env::temp_dir().join("payments_engine_what_if.jsonl");
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .args(["tests/data/dispute_after_withdrawal.csv", "--journal"])
+        .arg(&journal_path)
+        .assert()
+        .success();
+
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .arg("what-if")
+        .arg(&journal_path)
+        .args([
+            "--from-journal",
+            "--policy",
+            "tests/data/no_negative_available.toml",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Commands with a different outcome: 3\n",
+        ))
+        .stdout(predicate::str::contains(
+            "  ~ client 1: available +15.0000, held 0.0000, total +15.0000, locked true -> false\n",
+        ));
+
+    std::fs::remove_file(journal_path.with_extension("jsonl.checkpoints")).unwrap();
+    std::fs::remove_file(journal_path).unwrap();
+}
+
+#[test]
+fn test_generated_transactions_are_readable() {
+    let path = std::env::temp_dir().join("payments_engine_generated.csv");
+    let generate = |malformed_rate: &str| {
+        Command::cargo_bin("generate_transactions")
+            .unwrap()
+            .args(["--rows", "2000", "--clients", "20", "--seed", "42"])
+            .args([
+                "--duplicate-rate",
+                "0.01",
+                "--malformed-rate",
+                malformed_rate,
+            ])
+            .arg("--output")
+            .arg(&path)
+            .assert()
+            .success();
+    };
+
+    generate("0");
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .arg("validate")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("duplicate_tx"));
+
+    generate("0.05");
+    Command::cargo_bin("payments_engine")
+        .unwrap()
+        .arg("validate")
+        .arg(&path)
+        .assert()
+        .code(4);
+
+    std::fs::remove_file(path).unwrap();
+}
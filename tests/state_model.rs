@@ -0,0 +1,276 @@
+//! Property tests running random command sequences through `State` and a reference model of
+//! the same rules, written for clarity rather than speed.
+
+use payments_engine::{
+    engine::{policy::Policy, rejection::Rejection, state::State},
+    models::{command::Command, transaction::TransactionStatus},
+};
+use proptest::prelude::*;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct ModelAccount {
+    available: Decimal,
+    held: Decimal,
+    locked: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ModelDeposit {
+    client: u16,
+    amount: Decimal,
+    disputed: bool,
+}
+
+/// The engine rules spelled out one check at a time, in the order the engine makes them.
+#[derive(Debug, Default)]
+struct Model {
+    policy: Policy,
+    accounts: HashMap<u16, ModelAccount>,
+    /// Deposits that can still be referenced; a chargeback removes its deposit.
+    deposits: HashMap<u32, ModelDeposit>,
+    used_tx_ids: HashSet<u32>,
+}
+
+impl Model {
+    fn locked(&self, client: u16) -> bool {
+        self.accounts.get(&client).is_some_and(|acc| acc.locked)
+    }
+
+    /// A deposit of `client` that can be disputed, resolved or charged back.
+    fn deposit(&self, client: u16, tx: u32) -> Result<ModelDeposit, Rejection> {
+        let deposit = *self.deposits.get(&tx).ok_or(Rejection::UnknownTx)?;
+        match deposit.client == client {
+            true => Ok(deposit),
+            false => Err(Rejection::ClientMismatch),
+        }
+    }
+
+    fn apply(&mut self, cmd: &Command) -> Result<(), Rejection> {
+        match *cmd {
+            Command::Deposit {
+                client_id,
+                tx,
+                amount,
+            } => {
+                if amount <= Decimal::ZERO {
+                    return Err(Rejection::NonPositiveAmount);
+                }
+                if self.used_tx_ids.contains(&tx) {
+                    return Err(Rejection::DuplicateTx);
+                }
+                if self.locked(client_id) {
+                    return Err(Rejection::AccountLocked);
+                }
+                self.accounts.entry(client_id).or_default().available += amount;
+                self.deposits.insert(
+                    tx,
+                    ModelDeposit {
+                        client: client_id,
+                        amount,
+                        disputed: false,
+                    },
+                );
+                self.used_tx_ids.insert(tx);
+            }
+            Command::Withdrawal {
+                client_id,
+                tx,
+                amount,
+            } => {
+                if amount <= Decimal::ZERO {
+                    return Err(Rejection::NonPositiveAmount);
+                }
+                if self.used_tx_ids.contains(&tx) {
+                    return Err(Rejection::DuplicateTx);
+                }
+                if self.locked(client_id) {
+                    return Err(Rejection::AccountLocked);
+                }
+                let account = self.accounts.entry(client_id).or_default();
+                if account.available < amount {
+                    return Err(Rejection::InsufficientFunds);
+                }
+                account.available -= amount;
+                self.used_tx_ids.insert(tx);
+            }
+            Command::Dispute { client_id, tx } => {
+                if self.locked(client_id) {
+                    return Err(Rejection::AccountLocked);
+                }
+                let deposit = self.deposit(client_id, tx)?;
+                if deposit.disputed {
+                    return Err(Rejection::NotDisputable);
+                }
+                let account = self.accounts.get_mut(&client_id).unwrap();
+                if !self.policy.allow_negative_available && account.available < deposit.amount {
+                    return Err(Rejection::InsufficientFunds);
+                }
+                account.available -= deposit.amount;
+                account.held += deposit.amount;
+                self.deposits.get_mut(&tx).unwrap().disputed = true;
+            }
+            Command::Resolve { client_id, tx } => {
+                if self.locked(client_id) {
+                    return Err(Rejection::AccountLocked);
+                }
+                let deposit = self.deposit(client_id, tx)?;
+                if !deposit.disputed {
+                    return Err(Rejection::NotDisputed);
+                }
+                let account = self.accounts.get_mut(&client_id).unwrap();
+                account.held -= deposit.amount;
+                account.available += deposit.amount;
+                self.deposits.get_mut(&tx).unwrap().disputed = false;
+            }
+            Command::Chargeback { client_id, tx } => {
+                let deposit = self.deposit(client_id, tx)?;
+                if !deposit.disputed {
+                    return Err(Rejection::NotDisputed);
+                }
+                if self.locked(client_id) {
+                    return Err(Rejection::AccountLocked);
+                }
+                let account = self.accounts.get_mut(&client_id).unwrap();
+                account.held -= deposit.amount;
+                account.locked |= self.policy.lock_on_chargeback;
+                self.deposits.remove(&tx);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Money that entered or left each client through applied commands.
+#[derive(Debug, Default)]
+struct Flows {
+    deposited: Decimal,
+    withdrawn: Decimal,
+    charged_back: Decimal,
+}
+
+fn account(state: &State, client: u16) -> Option<ModelAccount> {
+    state.accounts.get(&client).map(|acc| ModelAccount {
+        available: acc.available,
+        held: acc.held,
+        locked: acc.locked,
+    })
+}
+
+/// Status of a transaction in the engine; a charged back deposit is no longer stored.
+fn status(state: &State, tx: u32, charged_back: &HashSet<u32>) -> Option<TransactionStatus> {
+    match charged_back.contains(&tx) {
+        true => Some(TransactionStatus::ChargedBack),
+        false => state.transaction(tx).map(|record| record.status.clone()),
+    }
+}
+
+fn is_allowed(before: &Option<TransactionStatus>, after: &Option<TransactionStatus>) -> bool {
+    use TransactionStatus::*;
+    matches!(
+        (before, after),
+        (None, None | Some(Normal))
+            | (Some(Normal), Some(Normal | Disputed))
+            | (Some(Disputed), Some(Disputed | Normal | ChargedBack))
+            | (Some(ChargedBack), Some(ChargedBack))
+    )
+}
+
+/// Few clients and transaction ids, so commands often collide, reference each other and hit
+/// every rejection.
+fn command() -> impl Strategy<Value = Command> {
+    let client = 1..=3u16;
+    let tx = 1..=12u32;
+    let amount = (-50..=2_000i64).prop_map(|cents| Decimal::new(cents, 2));
+    prop_oneof![
+        4 => (client.clone(), tx.clone(), amount.clone())
+            .prop_map(|(client_id, tx, amount)| Command::Deposit { client_id, tx, amount }),
+        2 => (client.clone(), tx.clone(), amount)
+            .prop_map(|(client_id, tx, amount)| Command::Withdrawal { client_id, tx, amount }),
+        2 => (client.clone(), tx.clone())
+            .prop_map(|(client_id, tx)| Command::Dispute { client_id, tx }),
+        1 => (client.clone(), tx.clone())
+            .prop_map(|(client_id, tx)| Command::Resolve { client_id, tx }),
+        1 => (client, tx).prop_map(|(client_id, tx)| Command::Chargeback { client_id, tx }),
+    ]
+}
+
+fn policy() -> impl Strategy<Value = Policy> {
+    (any::<bool>(), any::<bool>()).prop_map(|(allow_negative_available, lock_on_chargeback)| {
+        Policy {
+            allow_negative_available,
+            lock_on_chargeback,
+        }
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn state_matches_reference_model(
+        policy in policy(),
+        commands in prop::collection::vec(command(), 0..120),
+    ) {
+        let mut state = State::with_policy(policy);
+        let mut model = Model { policy, ..Model::default() };
+        let mut flows: HashMap<u16, Flows> = HashMap::new();
+        let mut frozen: HashMap<u16, ModelAccount> = HashMap::new();
+        let mut charged_back: HashSet<u32> = HashSet::new();
+        let mut accepted_deposits: HashMap<u32, Decimal> = HashMap::new();
+
+        for (step, cmd) in commands.iter().enumerate() {
+            let tx = cmd.tx();
+            let status_before = status(&state, tx, &charged_back);
+
+            let expected = model.apply(cmd);
+            let outcome = state.apply_command(cmd.clone());
+            prop_assert_eq!(outcome, expected, "step {}: {:?}", step, cmd);
+
+            if outcome.is_ok() {
+                let flow = flows.entry(cmd.client_id()).or_default();
+                match cmd {
+                    Command::Deposit { amount, .. } => {
+                        flow.deposited += amount;
+                        accepted_deposits.insert(tx, *amount);
+                    }
+                    Command::Withdrawal { amount, .. } => flow.withdrawn += amount,
+                    Command::Chargeback { .. } => {
+                        flow.charged_back += accepted_deposits[&tx];
+                        charged_back.insert(tx);
+                    }
+                    _ => {}
+                }
+            }
+
+            let status_after = status(&state, tx, &charged_back);
+            prop_assert!(
+                is_allowed(&status_before, &status_after),
+                "step {}: tx {} went from {:?} to {:?}", step, tx, status_before, status_after
+            );
+
+            prop_assert_eq!(state.accounts.len(), model.accounts.len());
+            for (&client, expected) in &model.accounts {
+                let actual = account(&state, client);
+                prop_assert_eq!(actual, Some(*expected), "step {}: client {}", step, client);
+
+                // total = deposits - withdrawals - chargebacks
+                let flow = flows.get(&client);
+                let net = flow.map_or(Decimal::ZERO, |f| f.deposited - f.withdrawn - f.charged_back);
+                prop_assert_eq!(expected.available + expected.held, net, "client {}", client);
+
+                // a locked account never changes again
+                match frozen.get(&client) {
+                    Some(locked) => prop_assert_eq!(expected, locked),
+                    None if expected.locked => {
+                        frozen.insert(client, *expected);
+                    }
+                    None => {}
+                }
+            }
+
+            prop_assert_eq!(state.check_invariants(), Ok(()));
+        }
+    }
+}